@@ -0,0 +1,45 @@
+//! Times building and rendering scenes of many spheres. With the BVH, building stays linear in
+//! the number of objects and render time grows roughly logarithmically.
+//!
+//! Run with `cargo bench --bench bvh`.
+
+use std::time::Instant;
+
+use nalgebra::Vector3;
+use tiny_raytracer::{object::Sphere, Framebuffer, Material, RenderSettings, Scene};
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 120;
+
+fn grid_scene(count: usize) -> Scene {
+    let side = (count as f32).sqrt().ceil() as usize;
+    let spacing = 20.0 / side as f32;
+    let mut scene = Scene::new();
+    for i in 0..count {
+        let (x, y) = ((i % side) as f32, (i / side) as f32);
+        let center = Vector3::new(x * spacing - 10.0, y * spacing - 10.0, -20.0);
+        let material = Material::color([0.8, 0.8, 0.8], 1.0);
+        scene.push_object(Sphere::new(center, spacing * 0.4, material));
+    }
+    scene
+}
+
+fn main() {
+    let settings = RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        ..RenderSettings::default()
+    };
+    for &count in &[100, 1_000, 10_000, 100_000] {
+        let start = Instant::now();
+        let scene = grid_scene(count);
+        let build = start.elapsed();
+
+        let start = Instant::now();
+        let mut fb = Framebuffer::new(WIDTH, HEIGHT);
+        scene.render(&mut fb, &settings);
+        let render = start.elapsed();
+
+        println!("{:>7} spheres: build {:>10.3?}, render {:>10.3?}", count, build, render);
+    }
+}
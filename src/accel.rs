@@ -0,0 +1,156 @@
+use nalgebra::Vector3;
+use crate::object::{Aabb, IntersectionInfo};
+
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        items: Vec<usize>,
+    },
+    Branch {
+        bounds: Aabb,
+        children: (usize, usize),
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the objects of a scene, referred to by index. Objects
+/// without a bounding box are kept aside and tested against every ray.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy from the bounding box of each object, splitting at the median
+    /// along the longest axis of the box centers.
+    pub fn new(boxes: &[Option<Aabb>]) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (idx, bounds) in boxes.iter().enumerate() {
+            match bounds {
+                Some(bounds) => bounded.push((idx, bounds.clone())),
+                None => unbounded.push(idx),
+            }
+        }
+
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            unbounded,
+        };
+        if !bounded.is_empty() {
+            bvh.build(&mut bounded);
+        }
+        bvh
+    }
+
+    /// Appends the subtree over `items` and returns its index; the root ends up at 0.
+    fn build(&mut self, items: &mut [(usize, Aabb)]) -> usize {
+        let bounds = items[1..]
+            .iter()
+            .fold(items[0].1.clone(), |acc, (_, bounds)| acc.union(bounds));
+        let node_idx = self.nodes.len();
+        if items.len() <= MAX_LEAF_SIZE {
+            self.nodes.push(Node::Leaf {
+                bounds,
+                items: items.iter().map(|&(idx, _)| idx).collect(),
+            });
+            return node_idx;
+        }
+
+        let centers: Vec<_> = items.iter().map(|(_, bounds)| bounds.center()).collect();
+        let min = centers.iter().fold(centers[0], |acc, c| acc.zip_map(c, f32::min));
+        let max = centers.iter().fold(centers[0], |acc, c| acc.zip_map(c, f32::max));
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        items.sort_unstable_by(|(_, a), (_, b)| {
+            a.center()[axis]
+                .partial_cmp(&b.center()[axis])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        // reserve the slot, children are filled in once they exist
+        self.nodes.push(Node::Leaf {
+            bounds: bounds.clone(),
+            items: Vec::new(),
+        });
+        let (left, right) = items.split_at_mut(items.len() / 2);
+        let children = (self.build(left), self.build(right));
+        self.nodes[node_idx] = Node::Branch { bounds, children };
+        node_idx
+    }
+
+    /// Nearest hit along the ray with normalized `dir`, where `intersect` tests the object with
+    /// the given index.
+    pub fn nearest<F>(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        intersect: F,
+    ) -> Option<IntersectionInfo>
+    where
+        F: Fn(usize) -> Option<IntersectionInfo>,
+    {
+        let closer = |nearest: Option<IntersectionInfo>, info: IntersectionInfo| {
+            // degenerate geometry can give NaN or infinite distances, which are never the hit
+            if !info.dist.is_finite() {
+                return nearest;
+            }
+            match nearest {
+                Some(best) if best.dist <= info.dist => Some(best),
+                _ => Some(info),
+            }
+        };
+
+        let mut nearest = self
+            .unbounded
+            .iter()
+            .filter_map(|&idx| intersect(idx))
+            .fold(None, closer);
+        if self.nodes.is_empty() {
+            return nearest;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            match node.bounds().hit_range(orig, dir) {
+                // behind the ray, or farther than what's already been hit
+                Some((t_near, t_far)) => {
+                    let best = nearest.as_ref().map(|info| info.dist).unwrap_or(f32::INFINITY);
+                    if t_far.is_sign_negative() || t_near > best {
+                        continue;
+                    }
+                },
+                None => continue,
+            }
+
+            match node {
+                Node::Leaf { items, .. } => {
+                    nearest = items.iter().filter_map(|&idx| intersect(idx)).fold(nearest, closer);
+                },
+                Node::Branch { children, .. } => {
+                    stack.push(children.0);
+                    stack.push(children.1);
+                },
+            }
+        }
+        nearest
+    }
+}
@@ -0,0 +1,351 @@
+use nalgebra::Vector3;
+use crate::{sampling::Sampler, Scene};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rays from an image plane `size` units tall.
+    Orthographic { size: f32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    position: Vector3<f32>,
+    look_at: Vector3<f32>,
+    up: Vector3<f32>,
+    fov: f32,
+    projection: Projection,
+    aperture: f32,
+    focus_distance: f32,
+    lens_samples: u32,
+    pixel_aspect: f32,
+    film_offset: (f32, f32),
+    // orthonormal basis, with `forward` pointing into the scene
+    right: Vector3<f32>,
+    true_up: Vector3<f32>,
+    forward: Vector3<f32>,
+}
+
+impl Camera {
+    /// A camera at `position` looking towards `look_at`, rolled so that `up` points upwards in
+    /// the image, with a vertical field of view of `fov` radians.
+    pub fn new(position: Vector3<f32>, look_at: Vector3<f32>, up: Vector3<f32>, fov: f32) -> Self {
+        let forward = (look_at - position).normalize();
+        let right = forward.cross(&up).normalize();
+        let true_up = right.cross(&forward);
+        Self {
+            position,
+            look_at,
+            up,
+            fov,
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: 1.0,
+            lens_samples: 1,
+            pixel_aspect: 1.0,
+            film_offset: (0.0, 0.0),
+            right,
+            true_up,
+            forward,
+        }
+    }
+
+    /// A camera looking along `direction` at the center of the scene, far enough back that its
+    /// bounding box fits in the field of view of `fov` radians, both ways for images at least
+    /// as wide as they are tall. Scenes without finite objects get a camera at the origin
+    /// looking along `direction`.
+    pub fn framing(scene: &Scene, direction: Vector3<f32>, fov: f32) -> Self {
+        let direction = direction.normalize();
+        // world up, unless we're looking straight up or down
+        let up = if direction.y.abs() > 0.999 {
+            Vector3::new(0.0, 0.0, -direction.y.signum())
+        } else {
+            Vector3::new(0.0, 1.0, 0.0)
+        };
+        match scene.bounding_box() {
+            Some(bounds) => {
+                let center = bounds.center();
+                let basis = Self::new(center - direction, center, up, fov);
+                let (min, max) = (bounds.min(), bounds.max());
+                let tan = f32::tan(fov / 2.0);
+                // back off until each corner of the box is inside the view
+                let distance = (0..8)
+                    .map(|i| {
+                        let pick = |bit: usize, axis: usize| {
+                            if i & bit == 0 { min[axis] } else { max[axis] }
+                        };
+                        let corner = Vector3::new(pick(1, 0), pick(2, 1), pick(4, 2)) - center;
+                        let extent = f32::max(
+                            corner.dot(&basis.right).abs(),
+                            corner.dot(&basis.true_up).abs(),
+                        );
+                        extent / tan - corner.dot(&direction)
+                    })
+                    .fold(1e-3, f32::max);
+                Self::new(center - direction * distance, center, up, fov)
+            },
+            None => Self::new(nalgebra::zero(), direction, up, fov),
+        }
+    }
+
+    /// Switches to a parallel projection with an image plane `size` units tall, ignoring the
+    /// field of view.
+    pub fn with_orthographic(self, size: f32) -> Self {
+        Self {
+            projection: Projection::Orthographic { size },
+            ..self
+        }
+    }
+
+    /// Blurs everything but the plane `focus_distance` ahead, as seen through a lens of radius
+    /// `aperture`. Each pixel averages `samples` rays through random points on the lens.
+    pub fn with_depth_of_field(self, aperture: f32, focus_distance: f32, samples: u32) -> Self {
+        Self {
+            aperture,
+            focus_distance,
+            lens_samples: u32::max(samples, 1),
+            ..self
+        }
+    }
+
+    /// Renders for pixels `pixel_aspect` times as wide as they are tall, such as those of
+    /// anamorphic footage, so that the image looks right once stretched to that shape.
+    pub fn with_pixel_aspect(self, pixel_aspect: f32) -> Self {
+        Self {
+            pixel_aspect,
+            ..self
+        }
+    }
+
+    /// Shifts the film sideways by `offset`, in units of half the image height, like the shift
+    /// of a tilt-shift lens. Moves what's in frame without changing the perspective, keeping
+    /// vertical lines parallel when looking level at tall buildings.
+    pub fn with_film_offset(self, offset: (f32, f32)) -> Self {
+        Self {
+            film_offset: offset,
+            ..self
+        }
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    pub fn look_at(&self) -> Vector3<f32> {
+        self.look_at
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.up
+    }
+
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Rays to average per pixel.
+    pub fn samples(&self) -> u32 {
+        if self.aperture > 0.0 { self.lens_samples } else { 1 }
+    }
+
+    /// Origin and direction of the ray through `(x, y)` of a `width` by `height` image,
+    /// measured in pixels from the top left corner. Pixel centers lie at half-integer
+    /// coordinates.
+    pub fn primary_ray(
+        &self,
+        x: f32,
+        y: f32,
+        width: usize,
+        height: usize,
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        let wf = width as f32;
+        let hf = height as f32;
+        let aspect = wf / hf * self.pixel_aspect;
+        // the view spans the height, and the width follows the aspect ratio so that pixels keep
+        // their shape at any resolution
+        let ndc_x = (2.0 * x / wf - 1.0) * aspect + self.film_offset.0;
+        let ndc_y = 1.0 - 2.0 * y / hf + self.film_offset.1;
+
+        match self.projection {
+            Projection::Perspective => {
+                let fov_half_tan = f32::tan(self.fov / 2.0);
+                let dir = self.right * (ndc_x * fov_half_tan)
+                    + self.true_up * (ndc_y * fov_half_tan)
+                    + self.forward;
+                (self.position, dir)
+            },
+            Projection::Orthographic { size } => {
+                let half_size = size / 2.0;
+                let orig = self.position
+                    + self.right * (ndc_x * half_size)
+                    + self.true_up * (ndc_y * half_size);
+                (orig, self.forward)
+            },
+        }
+    }
+
+    /// Like `primary_ray`, but starting from a random point on the lens when the camera has an
+    /// aperture.
+    pub(crate) fn sample_ray(
+        &self,
+        x: f32,
+        y: f32,
+        width: usize,
+        height: usize,
+        sampler: &mut Sampler,
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        let (orig, dir) = self.primary_ray(x, y, width, height);
+        if self.aperture <= 0.0 {
+            return (orig, dir);
+        }
+
+        // `dir` is one unit long along `forward`, so this lands on the plane of focus
+        let focus = orig + dir * self.focus_distance;
+        let (x, y) = sampler.unit_disk();
+        let lens_orig =
+            orig + self.right * (self.aperture * x) + self.true_up * (self.aperture * y);
+        (lens_orig, focus - lens_orig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{object::Sphere, Background, Framebuffer, Material, RenderSettings};
+
+    /// Renders glowing spheres of the given centers and radii on black.
+    fn render_spheres(
+        spheres: &[(Vector3<f32>, f32)],
+        camera: Camera,
+        width: usize,
+        height: usize,
+    ) -> Framebuffer {
+        let mut scene = Scene::new();
+        scene.set_background(Background::Solid([0.0; 3]));
+        for &(center, radius) in spheres {
+            let glow = Material::none().with_emission([1.0; 3]);
+            scene.push_object(Sphere::new(center, radius, glow));
+        }
+        scene.set_camera(camera);
+        let settings = RenderSettings { width, height, ..RenderSettings::default() };
+        scene.render(&mut Framebuffer::new(width, height), &settings)
+    }
+
+    /// Bounds of the lit pixels within the columns `xs`, as `(left, right, top, bottom)`
+    /// inclusive.
+    fn lit_bounds(image: &Framebuffer, xs: std::ops::Range<usize>) -> (usize, usize, usize, usize) {
+        let lit: Vec<_> = (0..image.height())
+            .flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .filter(|&(x, y)| image.get_pixel(x, y)[0] > 0.5)
+            .collect();
+        assert!(!lit.is_empty(), "nothing lit");
+        (
+            lit.iter().map(|&(x, _)| x).min().unwrap(),
+            lit.iter().map(|&(x, _)| x).max().unwrap(),
+            lit.iter().map(|&(_, y)| y).min().unwrap(),
+            lit.iter().map(|&(_, y)| y).max().unwrap(),
+        )
+    }
+
+    #[test]
+    fn looking_along_x() {
+        let camera = Camera::new(nalgebra::zero(), Vector3::x(), Vector3::y(), 1.0);
+        let (_, dir) = camera.primary_ray(7.5, 7.5, 15, 15);
+        assert!((dir.normalize() - Vector3::x()).norm() < 1e-6, "{:?}", dir);
+
+        let image = render_spheres(&[(Vector3::new(5.0, 0.0, 0.0), 1.0)], camera, 15, 15);
+        let (left, right, top, bottom) = lit_bounds(&image, 0..15);
+        assert_eq!((left + right, top + bottom), (14, 14));
+    }
+
+    #[test]
+    fn wide_image_keeps_spheres_round() {
+        let sphere = [(Vector3::new(0.0, 0.0, -5.0), 1.0)];
+        let camera = Camera::new(nalgebra::zero(), -Vector3::z(), Vector3::y(), 1.0);
+        let size = |width: usize| {
+            let image = render_spheres(&sphere, camera.clone(), width, 32);
+            let (left, right, top, bottom) = lit_bounds(&image, 0..width);
+            (right - left, bottom - top)
+        };
+        let (square, wide) = (size(32), size(64));
+        assert_eq!(square.0, square.1);
+        assert_eq!(wide, square);
+    }
+
+    #[test]
+    fn tiny_aperture_is_a_pinhole() {
+        let spheres = [(Vector3::new(-1.0, 0.0, -5.0), 1.0), (Vector3::new(1.5, 0.5, -9.0), 1.0)];
+        let pinhole = Camera::new(nalgebra::zero(), -Vector3::z(), Vector3::y(), 1.0);
+        let lens = pinhole.clone().with_depth_of_field(1e-5, 5.0, 8);
+        assert_eq!(lens.samples(), 8);
+
+        let wide = pinhole.clone().with_depth_of_field(0.2, 5.0, 8);
+
+        let sharp = render_spheres(&spheres, pinhole, 32, 32);
+        let through_lens = render_spheres(&spheres, lens, 32, 32);
+        // a tiny lens sees what the pinhole does
+        for (a, b) in sharp.buf().iter().zip(through_lens.buf()) {
+            assert!((a[0] - b[0]).abs() < 1e-3, "{:?} vs {:?}", a, b);
+        }
+        // while a wide one blurs the sphere out of focus
+        let blurred = render_spheres(&spheres, wide, 32, 32);
+        assert!(sharp.buf().iter().zip(blurred.buf()).any(|(a, b)| (a[0] - b[0]).abs() > 0.1));
+    }
+
+    #[test]
+    fn orthographic_ignores_depth() {
+        let spheres = [(Vector3::new(-2.0, 0.0, -5.0), 1.0), (Vector3::new(2.0, 0.0, -15.0), 1.0)];
+        let perspective = Camera::new(nalgebra::zero(), -Vector3::z(), Vector3::y(), 1.0);
+        let orthographic = perspective.clone().with_orthographic(4.0);
+        // widths of the near sphere, in the left half, and the far one, in the right half
+        let widths = |camera: Camera| {
+            let image = render_spheres(&spheres, camera, 64, 32);
+            let (near_left, near_right, _, _) = lit_bounds(&image, 0..32);
+            let (far_left, far_right, _, _) = lit_bounds(&image, 32..64);
+            (near_right - near_left, far_right - far_left)
+        };
+
+        let (near, far) = widths(orthographic);
+        assert_eq!(near, far);
+        let (near, far) = widths(perspective);
+        assert!(near > far * 2, "{} vs {}", near, far);
+    }
+
+
+    #[test]
+    fn framing_fits_a_unit_sphere() {
+        let mut scene = Scene::new();
+        scene.push_object(Sphere::new(nalgebra::zero(), 1.0, Material::none()));
+        // lit width and height, making sure there's black all around
+        let size = |direction: Vector3<f32>| {
+            let camera = Camera::framing(&scene, direction, 1.0);
+            let image = render_spheres(&[(nalgebra::zero(), 1.0)], camera, 32, 32);
+            let (left, right, top, bottom) = lit_bounds(&image, 0..32);
+            assert!(left > 0 && top > 0 && right < 31 && bottom < 31);
+            (right - left + 1, bottom - top + 1)
+        };
+        // head on, the box around the sphere is not much bigger than it
+        let (width, height) = size(-Vector3::z());
+        assert!(width >= 20 && height >= 20, "{} x {}", width, height);
+        // and at an angle, its corners stick out further
+        let (width, height) = size(Vector3::new(1.0, -0.5, -1.0));
+        assert!(width >= 16 && height >= 16, "{} x {}", width, height);
+    }
+
+
+    #[test]
+    fn film_offset_shifts_the_image() {
+        let sphere = [(Vector3::new(0.0, 0.0, -5.0), 1.0)];
+        let camera = Camera::new(nalgebra::zero(), -Vector3::z(), Vector3::y(), 1.0);
+        let centered = lit_bounds(&render_spheres(&sphere, camera.clone(), 32, 32), 0..32);
+        // a quarter of half the height is four pixels; moving the film right moves the sphere
+        // left in the image
+        let shifted = render_spheres(&sphere, camera.with_film_offset((0.25, 0.0)), 32, 32);
+        let (left, right, top, bottom) = lit_bounds(&shifted, 0..32);
+        assert_eq!((left + 4, right + 4, top, bottom), centered);
+    }
+}
@@ -1,7 +1,20 @@
+// the `Fail` derive implements the trait inside an anonymous const
+#![allow(non_local_definitions)]
+
 use failure::Fail;
 
 #[derive(Debug, Fail)]
 pub enum RenderError {
     #[fail(display = "encode error: {}", _0)]
     Encode(#[cause] png::EncodingError),
+    #[fail(display = "decode error: {}", _0)]
+    Decode(#[cause] png::DecodingError),
+    #[fail(display = "I/O error: {}", _0)]
+    Io(#[cause] std::io::Error),
+    #[fail(display = "parse error: {}", _0)]
+    Parse(String),
+    #[fail(display = "out of bounds: {}", _0)]
+    OutOfBounds(String),
+    #[fail(display = "invalid size: {}", _0)]
+    InvalidSize(String),
 }
@@ -2,15 +2,181 @@ use png::HasParameters;
 
 use crate::RenderError;
 
+#[derive(Debug)]
 pub struct Framebuffer {
     width: usize,
     height: usize,
     buf: Vec<[f32; 3]>,
+    alpha: Option<Vec<f32>>,
 }
 
-fn f32_to_u8(val: f32) -> u8 {
-    let val = f32::min(1.0, f32::max(0.0, val));
-    (255.0 * val) as u8
+/// Running sum of frames of the same scene, averaged into a progressively cleaner image.
+#[derive(Debug, Clone)]
+pub struct AccumBuffer {
+    width: usize,
+    height: usize,
+    sum: Vec<[f32; 3]>,
+    count: u32,
+}
+
+impl AccumBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            sum: vec![[0.0; 3]; width * height],
+            count: 0,
+        }
+    }
+
+    /// Frames added so far.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn add_frame(&mut self, fb: &Framebuffer) -> Result<(), RenderError> {
+        if fb.width != self.width || fb.height != self.height {
+            return Err(RenderError::InvalidSize(format!(
+                "{}x{} frame added to a {}x{} accumulation buffer",
+                fb.width,
+                fb.height,
+                self.width,
+                self.height,
+            )));
+        }
+        for (acc, pixel) in self.sum.iter_mut().zip(&fb.buf) {
+            for (acc, channel) in acc.iter_mut().zip(pixel) {
+                *acc += channel;
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Average of the frames added so far, black if there are none.
+    pub fn resolve(&self) -> Framebuffer {
+        let scale = if self.count == 0 { 0.0 } else { 1.0 / self.count as f32 };
+        let mut fb = Framebuffer::new(self.width, self.height);
+        for (pixel, sum) in fb.buf.iter_mut().zip(&self.sum) {
+            *pixel = [sum[0] * scale, sum[1] * scale, sum[2] * scale];
+        }
+        fb
+    }
+
+    /// Starts over, as when the camera or the scene changes.
+    pub fn clear(&mut self) {
+        self.sum.iter_mut().for_each(|pixel| *pixel = [0.0; 3]);
+        self.count = 0;
+    }
+}
+
+/// Gamma of typical sRGB displays.
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+/// Operator compressing unbounded HDR values into `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Reinhard's `c / (1 + c)`, per channel.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, per channel.
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(self, val: f32) -> f32 {
+        let val = f32::max(0.0, val);
+        match self {
+            ToneMap::Reinhard => val / (1.0 + val),
+            ToneMap::Aces => {
+                let mapped = val * (2.51 * val + 0.03) / (val * (2.43 * val + 0.59) + 0.14);
+                f32::min(1.0, mapped)
+            },
+        }
+    }
+}
+
+fn f32_to_u8(val: f32, gamma: f32) -> u8 {
+    let val = val.clamp(0.0, 1.0);
+    (255.0 * val.powf(1.0 / gamma)) as u8
+}
+
+fn f32_to_u16(val: f32, gamma: f32) -> u16 {
+    let val = val.clamp(0.0, 1.0);
+    (65535.0 * val.powf(1.0 / gamma)) as u16
+}
+
+/// Shared-exponent RGBE encoding of an unbounded color, as used by Radiance `.hdr` files.
+fn f32_to_rgbe(rgb: [f32; 3]) -> [u8; 4] {
+    let [r, g, b] = [f32::max(0.0, rgb[0]), f32::max(0.0, rgb[1]), f32::max(0.0, rgb[2])];
+    let max = f32::max(r, f32::max(g, b));
+    if max < 1e-32 {
+        return [0; 4];
+    }
+    // the largest channel gets a mantissa in [128, 256)
+    let exp = f32::floor(f32::log2(max)) as i32 + 1;
+    let scale = f32::powi(2.0, 8 - exp);
+    let mantissa = |c: f32| f32::min(255.0, c * scale) as u8;
+    [mantissa(r), mantissa(g), mantissa(b), (exp + 128) as u8]
+}
+
+fn crop_rows<T: Clone>(
+    buf: &[T],
+    width: usize,
+    (x, y): (usize, usize),
+    (w, h): (usize, usize),
+) -> Vec<T> {
+    buf.chunks(width)
+        .skip(y)
+        .take(h)
+        .flat_map(|row| row[x..x + w].iter().cloned())
+        .collect()
+}
+
+/// Rec. 709 relative luminance.
+pub(crate) fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Combines each `factor` by `factor` block of `buf` into one value with `average`.
+fn downsample_blocks<T: Copy, F: Fn(&[T]) -> T>(
+    buf: &[T],
+    width: usize,
+    height: usize,
+    factor: usize,
+    average: F,
+) -> Vec<T> {
+    let (w, h) = (width / factor, height / factor);
+    let mut block = Vec::with_capacity(factor * factor);
+    let mut out = Vec::with_capacity(w * h);
+    for by in 0..h {
+        for bx in 0..w {
+            block.clear();
+            for y in (by * factor)..((by + 1) * factor) {
+                let start = y * width + bx * factor;
+                block.extend_from_slice(&buf[start..start + factor]);
+            }
+            out.push(average(&block));
+        }
+    }
+    out
+}
+
+fn flip_rows<T>(buf: &mut [T], width: usize, height: usize) {
+    for row in 0..height / 2 {
+        let mirror = height - 1 - row;
+        // split so that both rows can be borrowed at once
+        let (top, bottom) = buf.split_at_mut(mirror * width);
+        top[row * width..(row + 1) * width].swap_with_slice(&mut bottom[..width]);
+    }
+}
+
+fn flip_columns<T>(buf: &mut [T], width: usize) {
+    if width == 0 {
+        return;
+    }
+    for row in buf.chunks_mut(width) {
+        row.reverse();
+    }
 }
 
 impl Framebuffer {
@@ -19,6 +185,7 @@ impl Framebuffer {
             width,
             height,
             buf: vec![[0.0; 3]; width * height],
+            alpha: None,
         }
     }
 
@@ -36,6 +203,7 @@ impl Framebuffer {
             width: self.width,
             height: self.height,
             buf: old,
+            alpha: self.alpha.take(),
         }
     }
 
@@ -47,18 +215,524 @@ impl Framebuffer {
         &mut self.buf
     }
 
+    /// Per-pixel coverage, or `None` for a fully opaque image.
+    pub fn alpha(&self) -> Option<&[f32]> {
+        self.alpha.as_ref().map(|alpha| &alpha[..])
+    }
+
+    pub fn set_alpha(&mut self, alpha: Option<Vec<f32>>) {
+        if let Some(alpha) = &alpha {
+            assert_eq!(alpha.len(), self.buf.len(), "one alpha value per pixel is required");
+        }
+        self.alpha = alpha;
+    }
+
+    fn index_of(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) out of bounds for {}x{} framebuffer",
+            x,
+            y,
+            self.width,
+            self.height,
+        );
+        y * self.width + x
+    }
+
+    /// Color at column `x` and row `y`, counted from the top left. Panics when out of bounds.
+    pub fn get_pixel(&self, x: usize, y: usize) -> [f32; 3] {
+        self.buf[self.index_of(x, y)]
+    }
+
+    /// Sets the color at column `x` and row `y`, counted from the top left. Panics when out of
+    /// bounds.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [f32; 3]) {
+        let idx = self.index_of(x, y);
+        self.buf[idx] = color;
+    }
+
+    /// Maps every channel of every pixel through `tone_map`.
+    pub fn tone_map(&mut self, tone_map: ToneMap) {
+        for pixel in &mut self.buf {
+            for channel in pixel.iter_mut() {
+                *channel = tone_map.apply(*channel);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.fill([0.0; 3]);
+    }
+
+    /// Sets every pixel to an opaque `color`.
+    pub fn fill(&mut self, color: [f32; 3]) {
+        for pixel in &mut self.buf {
+            *pixel = color;
+        }
+        self.alpha = None;
+    }
+
+    /// Mirrors the image upside down, in place.
+    pub fn flip_vertical(&mut self) {
+        flip_rows(&mut self.buf, self.width, self.height);
+        if let Some(alpha) = &mut self.alpha {
+            flip_rows(alpha, self.width, self.height);
+        }
+    }
+
+    /// Mirrors the image left to right, in place.
+    pub fn flip_horizontal(&mut self) {
+        flip_columns(&mut self.buf, self.width);
+        if let Some(alpha) = &mut self.alpha {
+            flip_columns(alpha, self.width);
+        }
+    }
+
+    /// Copies the `w` by `h` region with its top left corner at `(x, y)` into a new framebuffer.
+    pub fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Result<Self, RenderError> {
+        if x + w > self.width || y + h > self.height {
+            return Err(RenderError::OutOfBounds(format!(
+                "{}x{} region at ({}, {}) in {}x{} framebuffer",
+                w,
+                h,
+                x,
+                y,
+                self.width,
+                self.height,
+            )));
+        }
+        Ok(Self {
+            width: w,
+            height: h,
+            buf: crop_rows(&self.buf, self.width, (x, y), (w, h)),
+            alpha: self.alpha.as_ref().map(|alpha| crop_rows(alpha, self.width, (x, y), (w, h))),
+        })
+    }
+
+    /// Smooths out sampling noise by averaging each pixel with its neighbors up to `radius`
+    /// pixels away, edges and all. A cheap approximation, not a substitute for more samples; see
+    /// `denoise` for a variant that keeps edges sharp. Run it before tone mapping.
+    pub fn denoise_box(&mut self, radius: usize) {
+        // an infinitely wide edge term weighs every neighbor the same
+        self.denoise(radius, f32::INFINITY);
+    }
+
+    /// Smooths out sampling noise by averaging each pixel with its neighbors up to `radius`
+    /// pixels away, weighting down those whose luminance differs by much more than
+    /// `edge_sigma` so that edges stay sharp. A cheap bilateral filter, not a substitute for
+    /// more samples. Run it before tone mapping.
+    pub fn denoise(&mut self, radius: usize, edge_sigma: f32) {
+        let (width, height) = (self.width, self.height);
+        let luma: Vec<f32> = self.buf.iter().map(|&pixel| luminance(pixel)).collect();
+        let inv_two_sigma_sq = 1.0 / (2.0 * f32::max(edge_sigma * edge_sigma, 1e-12));
+        let denoised = (0..width * height)
+            .map(|idx| {
+                let (x, y) = (idx % width, idx / width);
+                let mut sum = [0.0; 3];
+                let mut total_weight = 0.0;
+                for ny in y.saturating_sub(radius)..usize::min(y + radius + 1, height) {
+                    for nx in x.saturating_sub(radius)..usize::min(x + radius + 1, width) {
+                        let neighbor = ny * width + nx;
+                        let diff = luma[neighbor] - luma[idx];
+                        let weight = f32::exp(-diff * diff * inv_two_sigma_sq);
+                        for (acc, channel) in sum.iter_mut().zip(&self.buf[neighbor]) {
+                            *acc += channel * weight;
+                        }
+                        total_weight += weight;
+                    }
+                }
+                // the pixel itself always has weight 1, so this never divides by zero
+                [sum[0] / total_weight, sum[1] / total_weight, sum[2] / total_weight]
+            })
+            .collect();
+        self.buf = denoised;
+    }
+
+    /// Box filters each `factor` by `factor` block of pixels into one, to resolve an image
+    /// rendered at `factor` times the resolution. Both dimensions must be multiples of `factor`.
+    pub fn downsample(&self, factor: usize) -> Result<Self, RenderError> {
+        if factor == 0 || !self.width.is_multiple_of(factor) || !self.height.is_multiple_of(factor) {
+            return Err(RenderError::InvalidSize(format!(
+                "{}x{} framebuffer isn't divisible by {}",
+                self.width,
+                self.height,
+                factor,
+            )));
+        }
+        let buf = downsample_blocks(&self.buf, self.width, self.height, factor, |block| {
+            let mut sum = [0.0; 3];
+            for pixel in block {
+                for (acc, channel) in sum.iter_mut().zip(pixel) {
+                    *acc += channel / block.len() as f32;
+                }
+            }
+            sum
+        });
+        let alpha = self.alpha.as_ref().map(|alpha| {
+            downsample_blocks(alpha, self.width, self.height, factor, |block| {
+                block.iter().sum::<f32>() / block.len() as f32
+            })
+        });
+        Ok(Self {
+            width: self.width / factor,
+            height: self.height / factor,
+            buf,
+            alpha,
+        })
+    }
+
+    pub fn read_png<R: std::io::Read>(r: R) -> Result<Self, RenderError> {
+        let mut decoder = png::Decoder::new(r);
+        // palettes and sub-byte depths come out as 8-bit samples
+        decoder.set(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info().map_err(RenderError::Decode)?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).map_err(RenderError::Decode)?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            png::ColorType::Indexed => {
+                return Err(RenderError::Parse("unexpanded indexed PNG".to_owned()));
+            },
+        };
+        let samples: Vec<f32> = match info.bit_depth {
+            png::BitDepth::Sixteen => data
+                .chunks(2)
+                .map(|bytes| f32::from(u16::from(bytes[0]) << 8 | u16::from(bytes[1])) / 65535.0)
+                .collect(),
+            _ => data.iter().map(|&byte| f32::from(byte) / 255.0).collect(),
+        };
+
+        let width = info.width as usize;
+        let height = info.height as usize;
+        let buf = samples
+            .chunks(channels)
+            .take(width * height)
+            .map(|pixel| match pixel {
+                [gray] | [gray, _] => [*gray; 3],
+                _ => [pixel[0], pixel[1], pixel[2]],
+            })
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            buf,
+            alpha: None,
+        })
+    }
+
+    /// Writes the image gamma encoded for display with `DEFAULT_GAMMA`.
     pub fn write_png<W: std::io::Write>(&self, w: W) -> Result<(), RenderError> {
+        self.write_png_with_gamma(w, DEFAULT_GAMMA)
+    }
+
+    /// Writes the image encoded with `gamma`, where 1.0 stores the values as they are.
+    pub fn write_png_with_gamma<W: std::io::Write>(&self, w: W, gamma: f32) -> Result<(), RenderError> {
         let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
         encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
         let mut writer = encoder.write_header().map_err(RenderError::Encode)?;
 
-        let conv: Vec<_> = self
-            .buf
-            .iter()
-            .map(|rgb| rgb.iter().map(|&v| f32_to_u8(v)))
-            .flatten()
-            .collect();
+        let conv = self.encode_rgb8(gamma);
         writer.write_image_data(&conv).map_err(RenderError::Encode)?;
         Ok(())
     }
+
+    /// Writes an RGBA PNG encoded with `DEFAULT_GAMMA`, with the coverage from `alpha` and
+    /// opaque pixels where there is none.
+    pub fn write_png_rgba<W: std::io::Write>(&self, w: W) -> Result<(), RenderError> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(RenderError::Encode)?;
+
+        let mut conv = Vec::with_capacity(self.buf.len() * 4);
+        for (idx, rgb) in self.buf.iter().enumerate() {
+            let alpha = self.alpha.as_ref().map(|alpha| alpha[idx]).unwrap_or(1.0);
+            conv.extend(rgb.iter().map(|&v| f32_to_u8(v, DEFAULT_GAMMA)));
+            // alpha is coverage, not a color, so it skips gamma encoding
+            conv.push(f32_to_u8(alpha, 1.0));
+        }
+        writer.write_image_data(&conv).map_err(RenderError::Encode)?;
+        Ok(())
+    }
+
+    /// Writes a 16-bit PNG encoded with `gamma`, for smooth gradients without banding.
+    pub fn write_png16<W: std::io::Write>(&self, w: W, gamma: f32) -> Result<(), RenderError> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set(png::ColorType::RGB).set(png::BitDepth::Sixteen);
+        let mut writer = encoder.write_header().map_err(RenderError::Encode)?;
+
+        // PNG stores multi-byte samples big-endian
+        let mut conv = Vec::with_capacity(self.buf.len() * 6);
+        for &v in self.buf.iter().flatten() {
+            conv.extend(&f32_to_u16(v, gamma).to_be_bytes());
+        }
+        writer.write_image_data(&conv).map_err(RenderError::Encode)?;
+        Ok(())
+    }
+
+    /// Writes the image as a Radiance `.hdr` file, keeping values above 1 for tone mapping
+    /// elsewhere.
+    pub fn write_hdr<W: std::io::Write>(&self, mut w: W) -> std::io::Result<()> {
+        write!(w, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.height, self.width)?;
+        let mut scanline = Vec::with_capacity(self.width * 4 + 4);
+        for row in self.buf.chunks(self.width) {
+            scanline.clear();
+            let pixels: Vec<_> = row.iter().map(|&rgb| f32_to_rgbe(rgb)).collect();
+            if self.width < 8 || self.width >= 0x8000 {
+                // too narrow or too wide for run-length scanlines, written flat
+                scanline.extend(pixels.iter().flatten());
+            } else {
+                // run-length scanline with each component in turn, in literal runs only
+                scanline.extend(&[2, 2, (self.width >> 8) as u8, self.width as u8]);
+                for component in 0..4 {
+                    let values: Vec<_> = pixels.iter().map(|pixel| pixel[component]).collect();
+                    for chunk in values.chunks(128) {
+                        scanline.push(chunk.len() as u8);
+                        scanline.extend(chunk);
+                    }
+                }
+            }
+            w.write_all(&scanline)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the image as a binary PPM (P6), gamma encoded with `DEFAULT_GAMMA` like
+    /// `write_png`.
+    pub fn write_ppm<W: std::io::Write>(&self, mut w: W) -> std::io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.encode_rgb8(DEFAULT_GAMMA))
+    }
+
+    /// Interleaved 8-bit RGB samples, row by row from the top, without gamma encoding.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.encode_rgb8(1.0)
+    }
+
+    fn encode_rgb8(&self, gamma: f32) -> Vec<u8> {
+        self.buf
+            .iter()
+            .flat_map(|rgb| rgb.iter().map(move |&v| f32_to_u8(v, gamma)))
+            .collect()
+    }
+}
+
+impl std::ops::Index<(usize, usize)> for Framebuffer {
+    type Output = [f32; 3];
+
+    fn index(&self, (x, y): (usize, usize)) -> &[f32; 3] {
+        &self.buf[self.index_of(x, y)]
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for Framebuffer {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut [f32; 3] {
+        let idx = self.index_of(x, y);
+        &mut self.buf[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::Sampler;
+
+    fn variance(fb: &Framebuffer) -> f32 {
+        let n = fb.buf.len() as f32;
+        let mean = fb.buf.iter().map(|pixel| pixel[0]).sum::<f32>() / n;
+        fb.buf.iter().map(|pixel| (pixel[0] - mean).powi(2)).sum::<f32>() / n
+    }
+
+    #[test]
+    fn gamma_brightens_mid_gray() {
+        let encoded = f32_to_u8(0.5, DEFAULT_GAMMA);
+        assert!((185..=189).contains(&encoded), "{}", encoded);
+        assert_eq!(f32_to_u8(0.5, 1.0), 127);
+    }
+
+    #[test]
+    fn ppm_header_and_bytes() {
+        let mut fb = Framebuffer::new(2, 1);
+        fb.set_pixel(0, 0, [1.0, 0.0, 1.0]);
+        fb.set_pixel(1, 0, [0.0, 1.0, 0.0]);
+        let mut out = Vec::new();
+        fb.write_ppm(&mut out).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\xff\x00\xff\x00\xff\x00");
+    }
+
+    #[test]
+    fn png16_depth_and_mid_gray() {
+        let mut fb = Framebuffer::new(1, 1);
+        fb.fill([0.5; 3]);
+        let mut out = Vec::new();
+        fb.write_png16(&mut out, 1.0).unwrap();
+
+        let mut decoder = png::Decoder::new(&out[..]);
+        // keep the samples 16 bits wide
+        decoder.set(png::Transformations::IDENTITY);
+        let (info, mut reader) = decoder.read_info().unwrap();
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!(u16::from(data[0]) << 8 | u16::from(data[1]), 32767);
+    }
+
+    #[test]
+    fn rgba_background_is_transparent() {
+        let scene = crate::SceneBuilder::new()
+            .sphere([0.0, 0.0, -4.0], 1.0, crate::Material::color([1.0; 3], 1.0))
+            .build();
+        let settings = crate::RenderSettings { width: 8, height: 8, ..Default::default() };
+        let image = scene.render(&mut Framebuffer::new(8, 8), &settings);
+        let mut out = Vec::new();
+        image.write_png_rgba(&mut out).unwrap();
+
+        let (info, mut reader) = png::Decoder::new(&out[..]).read_info().unwrap();
+        assert_eq!(info.color_type, png::ColorType::RGBA);
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        let alpha_at = |x: usize, y: usize| data[(y * 8 + x) * 4 + 3];
+        assert_eq!(alpha_at(0, 0), 0);
+        assert_eq!(alpha_at(4, 4), 255);
+    }
+
+    #[test]
+    fn rgbe_round_trip() {
+        let [r, g, b, e] = f32_to_rgbe([4.0, 2.0, 1.0]);
+        let scale = f32::powi(2.0, i32::from(e) - 128 - 8);
+        let decoded = [f32::from(r) * scale, f32::from(g) * scale, f32::from(b) * scale];
+        for (decoded, expected) in decoded.iter().zip(&[4.0, 2.0, 1.0]) {
+            // 8-bit mantissas relative to the brightest channel
+            assert!((decoded - expected).abs() <= 4.0 / 128.0, "{:?}", decoded);
+        }
+
+        let mut fb = Framebuffer::new(1, 1);
+        fb.fill([4.0, 2.0, 1.0]);
+        let mut out = Vec::new();
+        fb.write_hdr(&mut out).unwrap();
+        assert!(out.starts_with(b"#?RADIANCE\n"));
+        assert_eq!(&out[out.len() - 4..], &[r, g, b, e]);
+    }
+
+    #[test]
+    fn pixel_at_corner() {
+        let mut fb = Framebuffer::new(3, 2);
+        fb.set_pixel(2, 1, [0.25, 0.5, 0.75]);
+        assert_eq!(fb.get_pixel(2, 1), [0.25, 0.5, 0.75]);
+        assert_eq!(fb[(2, 1)], [0.25, 0.5, 0.75]);
+        assert_eq!(fb.buf()[5], [0.25, 0.5, 0.75]);
+
+        fb[(0, 0)] = [1.0; 3];
+        assert_eq!(fb.get_pixel(0, 0), [1.0; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn pixel_out_of_bounds() {
+        Framebuffer::new(3, 2).get_pixel(3, 0);
+    }
+
+    #[test]
+    fn crop_top_left() {
+        let mut fb = Framebuffer::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                fb.set_pixel(x, y, [x as f32, y as f32, 0.5]);
+            }
+        }
+        let cropped = fb.crop(0, 0, 2, 2).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (2, 2));
+        assert_eq!(
+            cropped.buf(),
+            &[[0.0, 0.0, 0.5], [1.0, 0.0, 0.5], [0.0, 1.0, 0.5], [1.0, 1.0, 0.5]],
+        );
+        assert!(fb.crop(3, 0, 2, 2).is_err());
+    }
+
+    #[test]
+    fn flips_move_corner() {
+        let mut fb = Framebuffer::new(3, 2);
+        fb.set_pixel(0, 0, [1.0; 3]);
+        fb.flip_vertical();
+        assert_eq!(fb.get_pixel(0, 1), [1.0; 3]);
+        fb.flip_horizontal();
+        assert_eq!(fb.get_pixel(2, 1), [1.0; 3]);
+        assert_eq!(fb.buf().iter().filter(|&&pixel| pixel == [1.0; 3]).count(), 1);
+    }
+
+    #[test]
+    fn fill_and_clear() {
+        let mut fb = Framebuffer::new(3, 2);
+        fb.fill([0.1, 0.2, 0.3]);
+        assert!(fb.buf().iter().all(|&pixel| pixel == [0.1, 0.2, 0.3]));
+        fb.clear();
+        assert!(fb.buf().iter().all(|&pixel| pixel == [0.0; 3]));
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        let mut fb = Framebuffer::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                fb.set_pixel(x, y, [(y * 4 + x) as f32; 3]);
+            }
+        }
+        let small = fb.downsample(2).unwrap();
+        assert_eq!(small.buf(), &[[2.5; 3], [4.5; 3], [10.5; 3], [12.5; 3]]);
+        assert!(fb.downsample(3).is_err());
+    }
+
+    #[test]
+    fn denoise_box_reduces_variance() {
+        let mut fb = Framebuffer::new(32, 32);
+        let mut sampler = Sampler::new(3);
+        for y in 0..32 {
+            for x in 0..32 {
+                let value = 0.5 + (sampler.next_f32() - 0.5) * 0.4;
+                fb.set_pixel(x, y, [value; 3]);
+            }
+        }
+        let noisy = variance(&fb);
+        fb.denoise_box(2);
+        assert!(variance(&fb) < noisy / 4.0, "{} -> {}", noisy, variance(&fb));
+    }
+
+    #[test]
+    fn accumulate_same_frame_twice() {
+        let mut fb = Framebuffer::new(2, 2);
+        fb.set_pixel(0, 0, [0.25, 0.5, 1.0]);
+        fb.set_pixel(1, 1, [2.0, 0.0, 0.125]);
+        let mut accum = AccumBuffer::new(2, 2);
+        accum.add_frame(&fb).unwrap();
+        accum.add_frame(&fb).unwrap();
+        assert_eq!(accum.count(), 2);
+        assert_eq!(accum.resolve().buf(), fb.buf());
+
+        assert!(accum.add_frame(&Framebuffer::new(3, 2)).is_err());
+    }
+
+    #[test]
+    fn reinhard_keeps_bright_values_below_one() {
+        let mut fb = Framebuffer::new(1, 1);
+        fb.fill([4.0, 1.0, 0.0]);
+        fb.tone_map(ToneMap::Reinhard);
+        let expected = [0.8, 0.5, 0.0];
+        let pixel = fb.get_pixel(0, 0);
+        assert!(pixel.iter().zip(&expected).all(|(p, e)| (p - e).abs() < 1e-6), "{:?}", pixel);
+        assert!(ToneMap::Aces.apply(4.0) < 1.0);
+    }
+
+    #[test]
+    fn rgb8_bytes() {
+        let mut fb = Framebuffer::new(3, 2);
+        fb.set_pixel(1, 1, [1.0, 0.5, 0.0]);
+        let bytes = fb.to_rgb8();
+        assert_eq!(bytes.len(), 3 * 2 * 3);
+        // row by row, without gamma
+        assert_eq!(&bytes[12..15], &[255, 127, 0]);
+        assert!(bytes[..12].iter().chain(&bytes[15..]).all(|&byte| byte == 0));
+    }
 }
@@ -1,12 +1,30 @@
+mod accel;
+mod camera;
 mod error;
 mod framebuffer;
 mod material;
 mod math;
 pub mod object;
+mod sampling;
 mod scene;
+mod texture;
 
+pub use camera::{Camera, Projection};
 pub use error::RenderError;
-pub use framebuffer::Framebuffer;
-pub use material::Material;
+pub use framebuffer::{AccumBuffer, Framebuffer, ToneMap, DEFAULT_GAMMA};
+pub use material::{Material, Texture, TextureFilter, WrapMode};
+pub use sampling::Sampler;
+pub use texture::NoiseTexture;
 
-pub use scene::{Light, Scene};
+pub use scene::{
+    AdaptiveSampling,
+    Background,
+    FalloffKind,
+    Light,
+    RenderMode,
+    RenderSettings,
+    Scene,
+    SceneBuilder,
+};
+#[cfg(feature = "serde")]
+pub use scene::from_json;
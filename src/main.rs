@@ -1,45 +1,126 @@
 use nalgebra::Vector3;
 use tiny_raytracer::{
-    object::{Checkerboard, Sphere},
-    Light,
+    object::Checkerboard,
     Material,
+    RenderSettings,
     Scene,
+    SceneBuilder,
 };
 
 const WIDTH: usize = 1024;
 const HEIGHT: usize = 768;
+const OUTPUT: &str = "output.png";
+
+fn ivory() -> Material {
+    Material::color([0.4, 0.4, 0.3], 0.6).with_specular(50.0, 0.3).with_reflect(0.1)
+}
+
+fn red_rubber() -> Material {
+    Material::color([0.3, 0.1, 0.1], 0.9).with_specular(10.0, 0.1)
+}
+
+fn mirror() -> Material {
+    Material::none().with_specular(1425.0, 10.0).with_reflect(0.8)
+}
+
+fn glass() -> Material {
+    Material::none().with_specular(125.0, 0.5).with_reflect(0.1).with_refract(1.5, 0.8)
+}
 
-const IVORY: Material = Material::color([0.4, 0.4, 0.3], 0.6).with_specular(50.0, 0.3).with_reflect(0.1);
-const RED_RUBBER: Material = Material::color([0.3, 0.1, 0.1], 0.9).with_specular(10.0, 0.1);
-const MIRROR: Material = Material::none().with_specular(1425.0, 10.0).with_reflect(0.8);
-const GLASS: Material = Material::none().with_specular(125.0, 0.5).with_reflect(0.1).with_refract(1.5, 0.8);
 const CHECKER_WHITE: Material = Material::color([1.0, 1.0, 1.0], 0.4);
 const CHECKER_ORANGE: Material = Material::color([1.0, 0.7, 0.3], 0.4);
 
+struct Args {
+    width: usize,
+    height: usize,
+    output: String,
+    scene: Option<String>,
+}
+
+fn parse_args() -> Result<Args, failure::Error> {
+    let mut args = Args {
+        width: WIDTH,
+        height: HEIGHT,
+        output: OUTPUT.to_string(),
+        scene: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let mut value = || {
+            iter.next().ok_or_else(|| failure::format_err!("missing value for {}", flag))
+        };
+        match &flag[..] {
+            "--width" => args.width = parse_dimension("--width", &value()?)?,
+            "--height" => args.height = parse_dimension("--height", &value()?)?,
+            "--output" => args.output = value()?,
+            "--scene" => args.scene = Some(value()?),
+            _ => {
+                return Err(failure::format_err!(
+                    "unknown argument {}\n\
+                     usage: tiny-raytracer [--width N] [--height N] [--output FILE] [--scene FILE]",
+                    flag,
+                ));
+            },
+        }
+    }
+    Ok(args)
+}
+
+fn parse_dimension(flag: &str, value: &str) -> Result<usize, failure::Error> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(failure::format_err!("{} must be a positive integer, got {:?}", flag, value)),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn load_scene(path: &str) -> Result<Scene, failure::Error> {
+    let file = std::fs::File::open(path)?;
+    Ok(tiny_raytracer::from_json(std::io::BufReader::new(file))?)
+}
+
+#[cfg(not(feature = "serde"))]
+fn load_scene(_path: &str) -> Result<Scene, failure::Error> {
+    Err(failure::format_err!("loading scene files requires the serde feature"))
+}
+
+fn demo_scene() -> Scene {
+    SceneBuilder::new()
+        .sphere([-3.0,  0.0, -16.0], 2.0, ivory())
+        .sphere([-1.0, -1.5, -12.0], 2.0, glass())
+        .sphere([ 1.5, -0.5, -18.0], 3.0, red_rubber())
+        .sphere([ 7.0,  5.0, -18.0], 4.0, mirror())
+        .object(
+            Checkerboard::new(
+                Vector3::from([-10.0, -4.0, -30.0]),
+                (Vector3::from([0.0, 0.0, 2.0]), Vector3::from([2.0, 0.0, 0.0])),
+                (10, 10),
+                (CHECKER_WHITE, CHECKER_ORANGE),
+            ),
+        )
+        .light([-20.0, 20.0,  20.0], 1.5)
+        .light([ 30.0, 50.0, -25.0], 1.0)
+        .light([ 30.0, 20.0,  30.0], 1.7)
+        .camera([0.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0], std::f32::consts::PI / 3.0)
+        .build()
+}
+
 fn main() -> Result<(), failure::Error> {
-    let mut framebuffer = tiny_raytracer::Framebuffer::new(WIDTH, HEIGHT);
-    let mut scene = Scene::new();
-
-    scene.push_object(Sphere::new(Vector3::from([-3.0,  0.0, -16.0]), 2.0, IVORY));
-    scene.push_object(Sphere::new(Vector3::from([-1.0, -1.5, -12.0]), 2.0, GLASS));
-    scene.push_object(Sphere::new(Vector3::from([ 1.5, -0.5, -18.0]), 3.0, RED_RUBBER));
-    scene.push_object(Sphere::new(Vector3::from([ 7.0,  5.0, -18.0]), 4.0, MIRROR));
-    scene.push_object(
-        Checkerboard::new(
-            Vector3::from([-10.0, -4.0, -30.0]),
-            (Vector3::from([0.0, 0.0, 2.0]), Vector3::from([2.0, 0.0, 0.0])),
-            (10, 10),
-            (CHECKER_WHITE, CHECKER_ORANGE),
-        ),
-    );
-
-    scene.push_light(Light::new(Vector3::from([-20.0, 20.0,  20.0]), 1.5));
-    scene.push_light(Light::new(Vector3::from([ 30.0, 50.0, -25.0]), 1.0));
-    scene.push_light(Light::new(Vector3::from([ 30.0, 20.0,  30.0]), 1.7));
-
-    scene.render(&mut framebuffer, WIDTH, HEIGHT, std::f32::consts::PI / 3.0);
-
-    let file = std::fs::File::create("output.png")?;
-    framebuffer.write_png(file)?;
+    let args = parse_args()?;
+    let scene = match &args.scene {
+        Some(path) => load_scene(path)?,
+        None => demo_scene(),
+    };
+
+    let mut framebuffer = tiny_raytracer::Framebuffer::new(args.width, args.height);
+    let settings = RenderSettings {
+        width: args.width,
+        height: args.height,
+        ..RenderSettings::default()
+    };
+    let image = scene.render(&mut framebuffer, &settings);
+
+    let file = std::fs::File::create(&args.output)?;
+    image.write_png(file)?;
     Ok(())
 }
@@ -1,20 +1,65 @@
+use std::sync::Arc;
+
+use nalgebra::Vector3;
+use crate::{framebuffer::Framebuffer, texture::NoiseTexture, RenderError};
+
 #[derive(Debug, Clone)]
 pub struct Material {
     pub(crate) diffuse: Option<Diffuse>,
     pub(crate) specular: Option<Specular>,
     pub(crate) reflect: Option<f32>,
+    pub(crate) reflect_roughness: f32,
     pub(crate) refract: Option<Refract>,
+    pub(crate) emission: Option<[f32; 3]>,
+    pub(crate) pbr: Option<Pbr>,
+    pub(crate) normal_map: Option<Texture>,
+    pub(crate) clearcoat: Option<Clearcoat>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Diffuse {
     pub(crate) kind: DiffuseKind,
     pub(crate) albedo: f32,
+    /// Standard deviation of the microfacet slopes in radians for the Oren-Nayar model, with 0
+    /// being plain Lambertian.
+    pub(crate) roughness: f32,
 }
 
 #[derive(Debug, Clone)]
 pub enum DiffuseKind {
     Color([f32; 3]),
+    Texture(Texture),
+    /// Evaluated at the hit's position in world space rather than its UV.
+    Noise(NoiseTexture),
+    /// Alternates between the two colors across squares `1 / scale` wide in UV space.
+    Checker {
+        color_a: [f32; 3],
+        color_b: [f32; 3],
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+}
+
+/// What a texture shows outside of the unit square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Tiles the image.
+    Repeat,
+    /// Extends the edge texels outwards.
+    Clamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Texture {
+    image: Arc<Framebuffer>,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    average: [f32; 3],
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -23,10 +68,29 @@ pub struct Specular {
     pub(crate) albedo: f32,
 }
 
+/// Metallic/roughness parameters shaded with a Cook-Torrance BRDF.
+#[derive(Debug, Clone, Copy)]
+pub struct Pbr {
+    pub(crate) base_color: [f32; 3],
+    pub(crate) metallic: f32,
+    pub(crate) roughness: f32,
+}
+
+/// A glossy transparent layer over the rest of the material, like lacquer or car paint.
+#[derive(Debug, Clone, Copy)]
+pub struct Clearcoat {
+    pub(crate) strength: f32,
+    pub(crate) roughness: f32,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Refract {
     pub(crate) index: f32,
     pub(crate) albedo: f32,
+    pub(crate) absorption: Option<[f32; 3]>,
+    /// Separate indices for the red, green and blue channels, overriding `index` for the
+    /// direction of the refracted rays.
+    pub(crate) dispersion: Option<[f32; 3]>,
 }
 
 impl Material {
@@ -35,37 +99,641 @@ impl Material {
             diffuse: None,
             specular: None,
             reflect: None,
+            reflect_roughness: 0.0,
             refract: None,
+            emission: None,
+            pbr: None,
+            normal_map: None,
+            clearcoat: None,
         }
     }
 
+    /// Plain matte mid-gray, a neutral stand-in for objects without a material of their own.
+    pub const fn default_diffuse() -> Self {
+        Self::color([0.5, 0.5, 0.5], 0.8)
+    }
+
     pub const fn color(diffuse: [f32; 3], albedo: f32) -> Self {
         Self {
-            diffuse: Some(Diffuse { kind: DiffuseKind::Color(diffuse), albedo }),
+            diffuse: Some(Diffuse {
+                kind: DiffuseKind::Color(diffuse),
+                albedo,
+                roughness: 0.0,
+            }),
+            specular: None,
+            reflect: None,
+            reflect_roughness: 0.0,
+            refract: None,
+            emission: None,
+            pbr: None,
+            normal_map: None,
+            clearcoat: None,
+        }
+    }
+
+    pub fn texture(texture: Texture, albedo: f32) -> Self {
+        Self {
+            diffuse: Some(Diffuse {
+                kind: DiffuseKind::Texture(texture),
+                albedo,
+                roughness: 0.0,
+            }),
+            specular: None,
+            reflect: None,
+            reflect_roughness: 0.0,
+            refract: None,
+            emission: None,
+            pbr: None,
+            normal_map: None,
+            clearcoat: None,
+        }
+    }
+
+    /// A checker pattern with `scale` squares along each unit of UV, so that it wraps around
+    /// bounded objects `scale` times.
+    pub const fn checker(color_a: [f32; 3], color_b: [f32; 3], scale: f32, albedo: f32) -> Self {
+        Self {
+            diffuse: Some(Diffuse {
+                kind: DiffuseKind::Checker { color_a, color_b, scale },
+                albedo,
+                roughness: 0.0,
+            }),
+            specular: None,
+            reflect: None,
+            reflect_roughness: 0.0,
+            refract: None,
+            emission: None,
+            pbr: None,
+            normal_map: None,
+            clearcoat: None,
+        }
+    }
+
+    pub fn noise(noise: NoiseTexture, albedo: f32) -> Self {
+        Self {
+            diffuse: Some(Diffuse {
+                kind: DiffuseKind::Noise(noise),
+                albedo,
+                roughness: 0.0,
+            }),
+            specular: None,
+            reflect: None,
+            reflect_roughness: 0.0,
+            refract: None,
+            emission: None,
+            pbr: None,
+            normal_map: None,
+            clearcoat: None,
+        }
+    }
+
+    /// A physically based material, replacing the diffuse and specular terms with a GGX
+    /// microfacet lobe over a Lambertian base.
+    pub const fn pbr(base_color: [f32; 3], metallic: f32, roughness: f32) -> Self {
+        Self {
+            diffuse: None,
             specular: None,
             reflect: None,
+            reflect_roughness: 0.0,
             refract: None,
+            emission: None,
+            pbr: Some(Pbr { base_color, metallic, roughness }),
+            normal_map: None,
+            clearcoat: None,
         }
     }
 
-    pub const fn with_specular(self, specular_exp: f32, albedo: f32) -> Self {
+    /// Shades the diffuse term with the Oren-Nayar model for rough surfaces like clay or the
+    /// moon, which look flatter and brighten towards the edges. `roughness` is the spread of
+    /// the surface slopes in radians, 0 being Lambertian. Has no effect without a diffuse term.
+    pub fn with_diffuse_roughness(self, roughness: f32) -> Self {
+        Self {
+            diffuse: self.diffuse.map(|diffuse| Diffuse { roughness, ..diffuse }),
+            ..self
+        }
+    }
+
+    pub fn with_specular(self, specular_exp: f32, albedo: f32) -> Self {
         Self {
             specular: Some(Specular { specular_exp, albedo }),
             ..self
         }
     }
 
-    pub const fn with_reflect(self, albedo: f32) -> Self {
+    pub fn with_reflect(self, albedo: f32) -> Self {
         Self {
             reflect: Some(albedo),
             ..self
         }
     }
 
-    pub const fn with_refract(self, index: f32, albedo: f32) -> Self {
+    /// Blurs reflections by scattering them within a cone that widens with `roughness`, from a
+    /// sharp mirror at 0 to the whole hemisphere at 1.
+    pub fn with_reflect_roughness(self, roughness: f32) -> Self {
+        Self {
+            reflect_roughness: roughness,
+            ..self
+        }
+    }
+
+    pub fn with_refract(self, index: f32, albedo: f32) -> Self {
+        Self {
+            refract: Some(Refract { index, albedo, absorption: None, dispersion: None }),
+            ..self
+        }
+    }
+
+    /// Absorbs light travelling through the interior, by `absorption` per unit distance in each
+    /// channel. Has no effect unless the material also refracts.
+    pub fn with_absorption(self, absorption: [f32; 3]) -> Self {
+        Self {
+            refract: self.refract.map(|refract| Refract { absorption: Some(absorption), ..refract }),
+            ..self
+        }
+    }
+
+    /// Refracts red, green and blue light by their own `indices`, splitting white light into
+    /// colors like a prism. The index given to `with_refract` still decides how much light is
+    /// reflected. Has no effect unless the material also refracts.
+    pub fn with_dispersion(self, indices: [f32; 3]) -> Self {
+        Self {
+            refract: self.refract.map(|refract| Refract { dispersion: Some(indices), ..refract }),
+            ..self
+        }
+    }
+
+    /// Perturbs the shading normal by a tangent space normal map, with x along the surface's
+    /// `u`, y along its `v` and z out of the surface, each remapped from `[0, 1]`.
+    pub fn with_normal_map(self, normal_map: Texture) -> Self {
+        Self {
+            normal_map: Some(normal_map),
+            ..self
+        }
+    }
+
+    /// Coats the material in a clear layer reflecting the lights and the surroundings, more so
+    /// at grazing angles, up to `strength` of the light. `roughness` from 0 to 1 widens the
+    /// highlights; reflections stay sharp.
+    pub fn with_clearcoat(self, strength: f32, roughness: f32) -> Self {
+        Self {
+            clearcoat: Some(Clearcoat { strength, roughness }),
+            ..self
+        }
+    }
+
+    /// Glows with `color`, regardless of the lights in the scene.
+    pub fn with_emission(self, color: [f32; 3]) -> Self {
+        Self {
+            emission: Some(color),
+            ..self
+        }
+    }
+
+    /// Diffuse color: the color itself for flat colors, and the average color of images and
+    /// checkers. `None` without a diffuse term, or for noise.
+    pub fn diffuse_color(&self) -> Option<[f32; 3]> {
+        match &self.diffuse.as_ref()?.kind {
+            DiffuseKind::Color(color) => Some(*color),
+            DiffuseKind::Texture(texture) => Some(texture.average()),
+            DiffuseKind::Checker { color_a, color_b, .. } => {
+                Some(((Vector3::from(*color_a) + Vector3::from(*color_b)) / 2.0).into())
+            },
+            DiffuseKind::Noise(_) => None,
+        }
+    }
+
+    pub fn diffuse_albedo(&self) -> Option<f32> {
+        self.diffuse.as_ref().map(|diffuse| diffuse.albedo)
+    }
+
+    /// Specular exponent and albedo.
+    pub fn specular(&self) -> Option<(f32, f32)> {
+        self.specular.as_ref().map(|specular| (specular.specular_exp, specular.albedo))
+    }
+
+    pub fn reflect(&self) -> Option<f32> {
+        self.reflect
+    }
+
+    /// Refractive index and albedo.
+    pub fn refract(&self) -> Option<(f32, f32)> {
+        self.refract.as_ref().map(|refract| (refract.index, refract.albedo))
+    }
+
+    pub fn emission(&self) -> Option<[f32; 3]> {
+        self.emission
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::none()
+    }
+}
+
+impl Diffuse {
+    /// Base color at a hit at `position` with texture coordinates `uv`.
+    pub(crate) fn color(&self, uv: (f32, f32), position: Vector3<f32>) -> [f32; 3] {
+        match &self.kind {
+            DiffuseKind::Color(color) => *color,
+            DiffuseKind::Texture(texture) => texture.sample(uv),
+            DiffuseKind::Noise(noise) => noise.sample(position),
+            DiffuseKind::Checker { color_a, color_b, scale } => {
+                let (u, v) = uv;
+                let parity = f32::floor(u * scale) as i64 + f32::floor(v * scale) as i64;
+                if parity.rem_euclid(2) == 0 { *color_a } else { *color_b }
+            },
+        }
+    }
+
+    /// Oren-Nayar reflectance relative to Lambertian, for light from `light` seen from `view`,
+    /// all unit vectors pointing away from the surface.
+    pub(crate) fn oren_nayar(
+        &self,
+        normal: Vector3<f32>,
+        view: Vector3<f32>,
+        light: Vector3<f32>,
+    ) -> f32 {
+        if self.roughness <= 0.0 {
+            return 1.0;
+        }
+        let sigma_sq = self.roughness * self.roughness;
+        let a = 1.0 - 0.5 * sigma_sq / (sigma_sq + 0.33);
+        let b = 0.45 * sigma_sq / (sigma_sq + 0.09);
+
+        let cos_i = f32::min(normal.dot(&light), 1.0);
+        let cos_r = f32::min(normal.dot(&view), 1.0);
+        if cos_i <= 0.0 || cos_r <= 0.0 {
+            return a;
+        }
+        // cosine of the azimuth between the two, in the tangent plane
+        let light_tangent = light - normal * cos_i;
+        let view_tangent = view - normal * cos_r;
+        let tangent_len = light_tangent.norm() * view_tangent.norm();
+        let cos_phi = if tangent_len > 1e-6 {
+            light_tangent.dot(&view_tangent) / tangent_len
+        } else {
+            0.0
+        };
+        let (theta_i, theta_r) = (f32::acos(cos_i), f32::acos(cos_r));
+        let alpha = f32::max(theta_i, theta_r);
+        let beta = f32::min(theta_i, theta_r);
+        a + b * f32::max(0.0, cos_phi) * f32::sin(alpha) * f32::tan(beta)
+    }
+}
+
+impl Pbr {
+    /// Light reflected towards `view` from a light of unit intensity in direction `light`, all
+    /// unit vectors pointing away from the surface.
+    pub(crate) fn shade(
+        &self,
+        normal: Vector3<f32>,
+        view: Vector3<f32>,
+        light: Vector3<f32>,
+    ) -> Vector3<f32> {
+        let n_dot_l = normal.dot(&light);
+        let n_dot_v = normal.dot(&view);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return nalgebra::zero();
+        }
+        let half = (light + view).normalize();
+        let n_dot_h = f32::max(0.0, normal.dot(&half));
+        let v_dot_h = f32::max(0.0, view.dot(&half));
+
+        let base_color = Vector3::from(self.base_color);
+        // keep a sliver of roughness, a perfect mirror makes the distribution a delta
+        let roughness = f32::max(self.roughness, 1e-3);
+        let alpha_sq = f32::powi(roughness * roughness, 2);
+
+        // GGX normal distribution
+        let denom = n_dot_h * n_dot_h * (alpha_sq - 1.0) + 1.0;
+        let distribution = alpha_sq / (std::f32::consts::PI * denom * denom);
+        // Smith geometry with the Schlick-GGX approximation for direct lighting
+        let k = f32::powi(roughness + 1.0, 2) / 8.0;
+        let g1 = |x: f32| x / (x * (1.0 - k) + k);
+        let geometry = g1(n_dot_l) * g1(n_dot_v);
+        // Schlick Fresnel, dielectrics reflecting 4% head-on and metals their base color
+        let f0 = Vector3::repeat(0.04) * (1.0 - self.metallic) + base_color * self.metallic;
+        let fresnel = f0 + (Vector3::repeat(1.0) - f0) * f32::powi(1.0 - v_dot_h, 5);
+
+        let specular = fresnel * (distribution * geometry / (4.0 * n_dot_l * n_dot_v));
+        let diffuse = (Vector3::repeat(1.0) - fresnel)
+            .component_mul(&base_color)
+            * ((1.0 - self.metallic) / std::f32::consts::PI);
+        // scaled by π so that light intensities mean the same as in the legacy model, where a
+        // white Lambertian surface facing a unit light shades to 1
+        (diffuse + specular) * (std::f32::consts::PI * n_dot_l)
+    }
+}
+
+impl Texture {
+    pub fn new(image: Framebuffer, filter: TextureFilter) -> Self {
+        assert!(image.width() > 0 && image.height() > 0, "texture image is empty");
+        let pixel_count = (image.width() * image.height()) as f32;
+        let mut average = [0.0; 3];
+        for pixel in image.buf() {
+            for (sum, channel) in average.iter_mut().zip(pixel) {
+                *sum += channel / pixel_count;
+            }
+        }
+        Self {
+            image: Arc::new(image),
+            filter,
+            wrap: WrapMode::Repeat,
+            average,
+        }
+    }
+
+    /// Sets what the texture shows outside of the unit square, repeating by default.
+    pub fn with_wrap(self, wrap: WrapMode) -> Self {
         Self {
-            refract: Some(Refract { index, albedo }),
+            wrap,
             ..self
         }
     }
+
+    pub fn from_png<R: std::io::Read>(r: R, filter: TextureFilter) -> Result<Self, RenderError> {
+        Ok(Self::new(Framebuffer::read_png(r)?, filter))
+    }
+
+    /// Mean color of the image.
+    pub fn average(&self) -> [f32; 3] {
+        self.average
+    }
+
+    /// Color at `(u, v)`, with `(0, 0)` at the bottom left of the image and `(1, 1)` at the top
+    /// right. Coordinates outside of the unit square follow the wrap mode.
+    pub fn sample(&self, (u, v): (f32, f32)) -> [f32; 3] {
+        let width = self.image.width();
+        let height = self.image.height();
+        // pixel centers sit at half-integer coordinates
+        let x = u * width as f32 - 0.5;
+        let y = (1.0 - v) * height as f32 - 0.5;
+        let wrap = |i: i64, len: usize| match self.wrap {
+            WrapMode::Repeat => i.rem_euclid(len as i64) as usize,
+            WrapMode::Clamp => i64::max(0, i64::min(i, len as i64 - 1)) as usize,
+        };
+        let texel = |x: i64, y: i64| {
+            let (x, y) = (wrap(x, width), wrap(y, height));
+            Vector3::from(self.image.buf()[y * width + x])
+        };
+
+        match self.filter {
+            TextureFilter::Nearest => texel(f32::round(x) as i64, f32::round(y) as i64).into(),
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (f32::floor(x), f32::floor(y));
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+                let bottom = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+                (top * (1.0 - fy) + bottom * fy).into()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{object::{Plane, Quad, Sphere}, scene::Scene};
+
+    #[test]
+    fn read_back_ivory() {
+        let ivory = Material::color([0.4, 0.4, 0.3], 0.6).with_specular(50.0, 0.3).with_reflect(0.1);
+        assert_eq!(ivory.diffuse_color(), Some([0.4, 0.4, 0.3]));
+        assert_eq!(ivory.diffuse_albedo(), Some(0.6));
+        assert_eq!(ivory.specular(), Some((50.0, 0.3)));
+        assert_eq!(ivory.reflect(), Some(0.1));
+        assert_eq!(ivory.refract(), None);
+        assert_eq!(ivory.emission(), None);
+    }
+
+    #[test]
+    fn default_is_none() {
+        let material = Material::default();
+        assert!(material.diffuse.is_none());
+        assert!(material.specular.is_none());
+        assert!(material.reflect.is_none());
+        assert!(material.refract.is_none());
+        assert!(material.emission.is_none());
+        assert!(material.pbr.is_none());
+        assert!(material.normal_map.is_none());
+        assert!(material.clearcoat.is_none());
+
+        assert_eq!(Material::default_diffuse().diffuse_color(), Some([0.5; 3]));
+    }
+
+    #[test]
+    fn checker_image_at_the_poles() {
+        let (red, green) = ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let mut image = Framebuffer::new(2, 2);
+        image.set_pixel(0, 0, red);
+        image.set_pixel(1, 0, green);
+        image.set_pixel(0, 1, green);
+        image.set_pixel(1, 1, red);
+        let texture = Texture::new(image, TextureFilter::Bilinear).with_wrap(WrapMode::Clamp);
+
+        // lit by ambient light alone, so the texture comes through unshaded
+        let mut scene = Scene::new();
+        scene.set_ambient([1.0; 3]);
+        scene.push_object(Sphere::new(nalgebra::zero(), 1.0, Material::texture(texture, 1.0)));
+        // from above (`y = 1`) or below (`y = -1`), offset along z
+        let pole = |y: f32, z: f32| {
+            scene.cast_ray(Vector3::new(0.0, 5.0 * y, z), -Vector3::y() * y, 1)
+        };
+
+        // just off the poles towards +z, `u = 0.75` is the middle of the right column, and
+        // towards -z the middle of the left one; `v` starts from 0 at the north pole
+        assert_eq!(pole(1.0, 0.2), red);
+        assert_eq!(pole(1.0, -0.2), green);
+        assert_eq!(pole(-1.0, 0.2), green);
+        assert_eq!(pole(-1.0, -0.2), red);
+    }
+
+    #[test]
+    fn green_glass_darkens_with_thickness() {
+        // index 1 keeps the rays straight, so the path inside is just the chord
+        let glass = Material::none().with_refract(1.0, 1.0).with_absorption([1.0, 0.0, 1.0]);
+        let mut scene = Scene::new();
+        scene.set_background(crate::Background::Solid([1.0; 3]));
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, glass));
+        let through = |x: f32| scene.cast_ray(Vector3::new(x, 0.0, 0.0), -Vector3::z(), 4);
+
+        let (center, edge) = (through(0.0), through(0.8));
+        for color in &[center, edge] {
+            assert!(color[1] > 0.9 && color[0] < color[1] && color[2] < color[1], "{:?}", color);
+        }
+        // 2 units of glass through the center, 1.2 near the edge
+        assert!((center[0] - f32::exp(-2.0) * center[1]).abs() < 0.02, "{:?}", center);
+        assert!((edge[0] - f32::exp(-1.2) * edge[1]).abs() < 0.02, "{:?}", edge);
+    }
+
+    #[test]
+    fn rougher_metal_spreads_the_highlight() {
+        let highlight_pixels = |roughness: f32| {
+            let mut scene = Scene::new();
+            scene.set_hdr(true);
+            scene.set_background(crate::Background::Solid([0.0; 3]));
+            let metal = Material::pbr([1.0, 1.0, 1.0], 1.0, roughness);
+            scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.5, metal));
+            scene.push_light(crate::Light::new(Vector3::new(0.0, 0.0, 5.0), 1.0));
+            let settings = crate::RenderSettings { width: 64, height: 64, ..Default::default() };
+            let image = scene.render(&mut Framebuffer::new(64, 64), &settings);
+
+            // pixels at least half as bright as the peak of the highlight
+            let peak = image.buf().iter().map(|pixel| pixel[0]).fold(0.0, f32::max);
+            image.buf().iter().filter(|pixel| pixel[0] >= peak / 2.0).count()
+        };
+        let (smooth, rough) = (highlight_pixels(0.2), highlight_pixels(0.6));
+        assert!(rough > smooth * 4, "smooth {} rough {}", smooth, rough);
+    }
+
+    #[test]
+    fn normal_map_dents_flat_floor() {
+        // the sides of a dent running along v slope down towards the middle, so the normals on
+        // the left half lean right and the ones on the right half lean left
+        let mut dent = Framebuffer::new(2, 1);
+        dent.set_pixel(0, 0, [0.75, 0.5, 0.933]);
+        dent.set_pixel(1, 0, [0.25, 0.5, 0.933]);
+        let floor = |material: Material| {
+            let mut scene = Scene::new();
+            // u along +x, facing up
+            let edges = (Vector3::x() * 2.0, -Vector3::z() * 2.0);
+            scene.push_object(Quad::new(Vector3::new(-1.0, 0.0, -4.0), edges, material));
+            // low on the right, grazing the floor
+            scene.push_light(crate::Light::new(Vector3::new(10.0, 1.0, -5.0), 1.0));
+            scene
+        };
+        let shade = |scene: &Scene, x: f32| {
+            let dir = (Vector3::new(x, 0.0, -5.0) - Vector3::new(0.0, 2.0, 0.0)).normalize();
+            scene.cast_ray(Vector3::new(0.0, 2.0, 0.0), dir, 1)[0]
+        };
+
+        let white = Material::color([1.0; 3], 1.0);
+        let flat = floor(white.clone());
+        let dented = floor(white.with_normal_map(Texture::new(dent, TextureFilter::Nearest)));
+        // the far side of the dent turns towards the light, the near side away from it
+        assert!(shade(&dented, -0.5) > shade(&flat, -0.5) * 2.0);
+        assert!(shade(&dented, 0.5) < shade(&flat, 0.5) * 0.5);
+    }
+
+    #[test]
+    fn rough_mirror_blurs_reflection() {
+        let render = |mirror: Material| {
+            let mut scene = Scene::new();
+            scene.set_background(crate::Background::Solid([0.0; 3]));
+            scene.push_object(Plane::new(Vector3::new(0.0, 0.0, -5.0), Vector3::z(), mirror));
+            let glow = Material::none().with_emission([1.0; 3]);
+            scene.push_object(Sphere::new(Vector3::new(1.0, 0.0, -3.0), 0.3, glow));
+            let settings = crate::RenderSettings { width: 32, height: 32, ..Default::default() };
+            scene.render(&mut Framebuffer::new(32, 32), &settings)
+        };
+        let partial = |image: &Framebuffer| {
+            image.buf().iter().filter(|pixel| pixel[0] > 0.02 && pixel[0] < 0.9).count()
+        };
+
+        let sharp = render(Material::none().with_reflect(1.0));
+        let smooth = render(Material::none().with_reflect(1.0).with_reflect_roughness(0.0));
+        assert_eq!(smooth.buf(), sharp.buf());
+        let rough = render(Material::none().with_reflect(1.0).with_reflect_roughness(0.3));
+        // the ball seen in the mirror gets a soft edge
+        let (blurred, crisp) = (partial(&rough), partial(&sharp));
+        assert!(blurred > crisp + 10, "{} vs {}", blurred, crisp);
+    }
+
+
+    #[test]
+    fn rough_sphere_has_a_bright_rim() {
+        // lit from the camera, where the Oren-Nayar model differs most from Lambertian
+        let edge_to_center = |material: Material| {
+            let mut scene = Scene::new();
+            scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, material));
+            scene.push_light(crate::Light::new(Vector3::zeros(), 1.0));
+            let shade = |x: f32| {
+                let dir = Vector3::new(x, 0.0, -5.0).normalize();
+                scene.cast_ray(Vector3::zeros(), dir, 1)[0]
+            };
+            shade(0.9) / shade(0.0)
+        };
+        let clay = Material::color([0.8; 3], 1.0);
+        let lambert = edge_to_center(clay.clone());
+        let rough = edge_to_center(clay.with_diffuse_roughness(1.0));
+        assert!(lambert < 0.6 && rough > lambert * 1.5, "{} vs {}", lambert, rough);
+    }
+
+
+    #[test]
+    fn checker_alternates_around_a_sphere() {
+        let (red, green) = ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let mut scene = Scene::new();
+        scene.set_ambient([1.0; 3]);
+        let checker = Material::checker(red, green, 4.0, 1.0);
+        scene.push_object(Sphere::new(nalgebra::zero(), 1.0, checker));
+
+        // around the sphere just above the equator, in the middle of each of 16 slices, four
+        // to a square
+        let colors = (0..16)
+            .map(|i| {
+                let angle = (i as f32 + 0.5) / 16.0 * std::f32::consts::PI * 2.0;
+                let orig = Vector3::new(f32::cos(angle) * 5.0, 0.2, f32::sin(angle) * 5.0);
+                scene.cast_ray(orig, Vector3::new(-orig.x, 0.0, -orig.z), 1)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(colors.iter().filter(|&&color| color == red).count(), 8);
+        assert_eq!(colors.iter().filter(|&&color| color == green).count(), 8);
+        let changes = (0..16).filter(|&i| colors[i] != colors[(i + 1) % 16]).count();
+        assert_eq!(changes, 4);
+    }
+
+
+    #[test]
+    fn clearcoat_adds_highlight_and_reflection() {
+        let scene_with = |material: Material| {
+            let mut scene = Scene::new();
+            scene.set_hdr(true);
+            scene.set_background(crate::Background::Solid([0.0, 0.0, 1.0]));
+            scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, material));
+            scene.push_light(crate::Light::new(Vector3::zeros(), 1.0));
+            scene
+        };
+        let shade = |scene: &Scene, x: f32| {
+            scene.cast_ray(Vector3::zeros(), Vector3::new(x, 0.0, -5.0).normalize(), 2)
+        };
+        let paint = Material::color([0.8, 0.1, 0.1], 1.0);
+        let plain = scene_with(paint.clone());
+        let coated = scene_with(paint.with_clearcoat(1.0, 0.0));
+
+        // a faint reflection of the light where the sphere faces it head on, and only there
+        let gain = |x: f32| shade(&coated, x)[1] - shade(&plain, x)[1];
+        assert!(gain(0.0) > 0.02 && gain(0.5) < gain(0.0) * 0.1, "{} {}", gain(0.0), gain(0.5));
+        // and the blue sky towards the edges, where a bare diffuse surface has none of it
+        assert_eq!(shade(&plain, 0.9)[2], shade(&plain, 0.9)[1]);
+        assert!(shade(&coated, 0.9)[2] > shade(&coated, 0.9)[1] + 0.05);
+    }
+
+
+    #[test]
+    fn nearest_snaps_and_bilinear_blends() {
+        let texture = |filter: TextureFilter| {
+            let mut image = Framebuffer::new(2, 2);
+            image.set_pixel(0, 0, [1.0, 0.0, 0.0]);
+            image.set_pixel(1, 0, [0.0, 1.0, 0.0]);
+            image.set_pixel(0, 1, [0.0, 0.0, 1.0]);
+            image.set_pixel(1, 1, [1.0, 1.0, 1.0]);
+            Texture::new(image, filter)
+        };
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-5);
+
+        // 0.3 texels right of and below the center of the top left texel
+        let uv = (0.4, 0.6);
+        assert_eq!(texture(TextureFilter::Nearest).sample(uv), [1.0, 0.0, 0.0]);
+        let bilinear = texture(TextureFilter::Bilinear);
+        let color = bilinear.sample(uv);
+        assert!(close(color, [0.58, 0.3, 0.3]), "{:?}", color);
+
+        // one texture width to the right is the same point again, unless clamped to the edge
+        assert!(close(bilinear.sample((1.4, 0.6)), color));
+        let clamped = bilinear.with_wrap(WrapMode::Clamp).sample((1.4, 0.6));
+        assert!(close(clamped, [0.3, 1.0, 0.3]), "{:?}", clamped);
+    }
 }
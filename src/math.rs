@@ -4,17 +4,328 @@ pub fn reflect(a: Vector3<f32>, n: Vector3<f32>) -> Vector3<f32> {
     a - a.dot(&n) * 2.0 * n
 }
 
+/// Ray parameter where a ray with normalized `dir` crosses the plane through `point`, if it
+/// does so in front of `orig`.
+pub fn intersect_plane(
+    orig: Vector3<f32>,
+    dir: Vector3<f32>,
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+) -> Option<f32> {
+    let denom = dir.dot(&normal);
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    let t = (point - orig).dot(&normal) / denom;
+    if t.is_sign_negative() { None } else { Some(t) }
+}
+
+/// Coordinates of `p` along the two perpendicular in-plane `axes`, in units of their lengths.
+pub fn plane_coords(p: Vector3<f32>, axes: (Vector3<f32>, Vector3<f32>)) -> (f32, f32) {
+    (p.dot(&axes.0) / axes.0.dot(&axes.0), p.dot(&axes.1) / axes.1.dot(&axes.1))
+}
+
+/// Flips `normal` if needed so that it faces against `dir`.
+pub fn face_forward(normal: Vector3<f32>, dir: Vector3<f32>) -> Vector3<f32> {
+    if normal.dot(&dir).is_sign_positive() { -normal } else { normal }
+}
+
+/// Origin for a ray leaving `hit` in `dir`, nudged `bias` along `normal` to the side `dir` points
+/// to so that it doesn't hit the surface it starts on.
+pub fn offset_origin(
+    hit: Vector3<f32>,
+    normal: Vector3<f32>,
+    dir: Vector3<f32>,
+    bias: f32,
+) -> Vector3<f32> {
+    if dir.dot(&normal).is_sign_negative() {
+        hit - normal * bias
+    } else {
+        hit + normal * bias
+    }
+}
+
+/// Two unit vectors completing the unit vector `n` to a right-handed orthonormal basis, with
+/// `e0 × e1 = n`.
+pub fn orthonormal_basis(n: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    // any axis not too close to `n` will do
+    let helper = if n.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let e0 = helper.cross(&n).normalize();
+    (e0, n.cross(&e0))
+}
+
+/// Like `orthonormal_basis`, without branches or normalization (Duff et al., "Building an
+/// Orthonormal Basis, Revisited"), for sampling directions around a normal where the
+/// orientation of the tangents doesn't matter. Texture coordinates stay on
+/// `orthonormal_basis` so that their layout doesn't change.
+pub fn onb_from_normal(n: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let sign = 1.0f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    (
+        Vector3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        Vector3::new(b, sign + n.y * n.y * a, -n.y),
+    )
+}
+
+/// Angle of `p` around the axis perpendicular to `basis`, mapped to `[0, 1]`.
+pub fn azimuth(p: Vector3<f32>, basis: (Vector3<f32>, Vector3<f32>)) -> f32 {
+    0.5 + f32::atan2(p.dot(&basis.1), p.dot(&basis.0)) / (2.0 * std::f32::consts::PI)
+}
+
+/// Unit direction in which `azimuth` around `axis` increases at `p`, falling back to any
+/// tangent of `normal` on the axis itself.
+pub fn azimuth_tangent(axis: Vector3<f32>, p: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    let tangent = axis.cross(&p);
+    if tangent.norm_squared() < 1e-12 {
+        orthonormal_basis(normal.normalize()).0
+    } else {
+        tangent.normalize()
+    }
+}
+
+/// UV of a point on the unit sphere, with `u = 0.5 + atan2(z, x) / 2π` the longitude around
+/// the y axis and `v = 0.5 - asin(y) / π` the latitude down from the north pole.
+///
+/// The +x point of the equator maps to `(0.5, 0.5)`, and `u` grows towards +z, so the seam
+/// where `u` wraps from 1 back to 0 runs through -x. `v` runs from 0 at the north pole (+y) to
+/// 1 at the south pole (-y), like the rows of an equirectangular image read from the top; at
+/// the poles every `u` meets, and the pole itself reports `u = 0.5`.
+pub fn sphere_uv(n: Vector3<f32>) -> (f32, f32) {
+    let u = azimuth(n, (Vector3::x(), Vector3::z()));
+    let v = 0.5 - f32::asin(n.y.clamp(-1.0, 1.0)) / std::f32::consts::PI;
+    (u, v)
+}
+
 pub fn refract(i: Vector3<f32>, n: Vector3<f32>, ni: f32, nr: f32) -> Vector3<f32> {
+    match refract_ex(i, n, ni, nr) {
+        Refraction::Transmit(dir) => dir,
+        // total reflection
+        Refraction::TotalInternal => -reflect(i, n),
+    }
+}
+
+/// Outcome of a ray meeting the boundary between two media.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Refraction {
+    /// Passes through, bent into this direction.
+    Transmit(Vector3<f32>),
+    /// Arrives too steeply to leave the denser medium, and is reflected entirely.
+    TotalInternal,
+}
+
+/// Like `refract`, but telling total internal reflection apart instead of returning a
+/// reflected direction.
+pub fn refract_ex(i: Vector3<f32>, n: Vector3<f32>, ni: f32, nr: f32) -> Refraction {
     let cos_i = -i.dot(&n);
     if cos_i.is_sign_negative() {
-        return refract(i, -n, nr, ni);
+        return refract_ex(i, -n, nr, ni);
     }
     let eta = ni / nr;
     let cos_r_sq = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
     if cos_r_sq.is_sign_negative() {
-        // total reflection
-        -reflect(i, n)
+        Refraction::TotalInternal
     } else {
-        i * eta + n * (eta * cos_i - f32::sqrt(cos_r_sq))
+        Refraction::Transmit(i * eta + n * (eta * cos_i - f32::sqrt(cos_r_sq)))
+    }
+}
+
+/// `refract`, along with the fraction of the light reflected off the boundary instead of
+/// passing through it.
+pub fn refract_fresnel(i: Vector3<f32>, n: Vector3<f32>, ni: f32, nr: f32) -> (Vector3<f32>, f32) {
+    let cos_i = -i.dot(&n);
+    if cos_i.is_sign_negative() {
+        return refract_fresnel(i, -n, nr, ni);
+    }
+    (refract(i, n, ni, nr), fresnel(cos_i, ni / nr))
+}
+
+/// Hermite interpolation from 0 at `edge_0` to 1 at `edge_1`, clamped outside of them.
+pub fn smoothstep(edge_0: f32, edge_1: f32, x: f32) -> f32 {
+    let t = ((x - edge_0) / (edge_1 - edge_0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Reflectance of a dielectric boundary for a ray arriving at `cos_i` to the normal, passing
+/// between media with the index ratio `eta = ni / nr`. Same as `schlick`.
+pub fn fresnel(cos_i: f32, eta: f32) -> f32 {
+    schlick(cos_i, eta)
+}
+
+/// Schlick's approximation of the reflectance of a dielectric boundary, for a ray arriving at
+/// `cos` to the normal and passing between media with the index ratio `ref_idx = ni / nr`.
+/// Reaches 1 at grazing incidence and under total reflection.
+pub fn schlick(cos: f32, ref_idx: f32) -> f32 {
+    let r0 = f32::powi((1.0 - ref_idx) / (1.0 + ref_idx), 2);
+    let cos = if ref_idx > 1.0 {
+        // leaving the denser medium, where the transmitted angle is the larger one
+        let sin_t_sq = ref_idx * ref_idx * (1.0 - cos * cos);
+        if sin_t_sq > 1.0 {
+            // total reflection
+            return 1.0;
+        }
+        f32::sqrt(1.0 - sin_t_sq)
+    } else {
+        cos
+    };
+    r0 + (1.0 - r0) * f32::powi(1.0 - cos, 5)
+}
+
+/// Real roots of `x³ + a x² + b x + c = 0`.
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // depress with x = y - a/3 into y³ + p y + q = 0
+    let shift = a / 3.0;
+    let p = b - a * shift;
+    let q = 2.0 * shift * shift * shift - b * shift + c;
+
+    let disc = q * q / 4.0 + p * p * p / 27.0;
+    if disc > 0.0 {
+        let disc_sqrt = f64::sqrt(disc);
+        let y = f64::cbrt(-q / 2.0 + disc_sqrt) + f64::cbrt(-q / 2.0 - disc_sqrt);
+        vec![y - shift]
+    } else if p == 0.0 {
+        vec![-shift]
+    } else {
+        // three real roots, trigonometric form
+        let r = f64::sqrt(-p / 3.0);
+        let phi = f64::acos((-q / (2.0 * r * r * r)).clamp(-1.0, 1.0));
+        (0..3)
+            .map(|k| 2.0 * r * f64::cos((phi - 2.0 * std::f64::consts::PI * k as f64) / 3.0) - shift)
+            .collect()
+    }
+}
+
+/// Real roots of `x⁴ + a x³ + b x² + c x + d = 0`, by Ferrari's method followed by a couple of
+/// Newton steps to clean up the precision lost along the way.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // depress with x = y - a/4 into y⁴ + p y² + q y + r = 0
+    let shift = a / 4.0;
+    let a_sq = a * a;
+    let p = b - 3.0 * a_sq / 8.0;
+    let q = c - a * b / 2.0 + a_sq * a / 8.0;
+    let r = d - a * c / 4.0 + a_sq * b / 16.0 - 3.0 * a_sq * a_sq / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    let mut push_quadratic = |b: f64, c: f64| {
+        let disc = b * b - 4.0 * c;
+        if disc >= 0.0 {
+            let disc_sqrt = f64::sqrt(disc);
+            roots.push((-b - disc_sqrt) / 2.0);
+            roots.push((-b + disc_sqrt) / 2.0);
+        }
+    };
+
+    if q.abs() < 1e-12 {
+        // biquadratic, solve for y²
+        let disc = p * p - 4.0 * r;
+        if disc >= 0.0 {
+            let disc_sqrt = f64::sqrt(disc);
+            for &y_sq in &[(-p - disc_sqrt) / 2.0, (-p + disc_sqrt) / 2.0] {
+                push_quadratic(0.0, -y_sq);
+            }
+        }
+    } else {
+        // resolvent cubic 8m³ + 8p m² + (2p² - 8r) m - q² = 0 always has a positive root,
+        // which turns both sides of (y² + p/2 + m)² = 2m y² - q y + ... into perfect squares
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return Vec::new();
+        }
+        let s = f64::sqrt(2.0 * m);
+        push_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+        push_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+    }
+
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - shift;
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + c) * x + d;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+                if df != 0.0 {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn sphere_uv_landmarks() {
+        let (u, v) = sphere_uv(Vector3::x());
+        assert_close(u, 0.5);
+        assert_close(v, 0.5);
+        let (u, _) = sphere_uv(Vector3::z());
+        assert_close(u, 0.75);
+        let (_, v) = sphere_uv(Vector3::y());
+        assert_close(v, 0.0);
+        let (_, v) = sphere_uv(-Vector3::y());
+        assert_close(v, 1.0);
+    }
+
+    #[test]
+    fn fresnel_grows_towards_grazing() {
+        // air to glass
+        let eta = 1.0 / 1.5;
+        assert_close(fresnel(1.0, eta), 0.04);
+        let grazing = fresnel(0.05, eta);
+        assert!(grazing > 0.7, "{}", grazing);
+        assert!(fresnel(0.5, eta) < grazing);
+    }
+
+    #[test]
+    fn onb_is_orthonormal() {
+        let normals = [
+            Vector3::x(),
+            Vector3::z(),
+            -Vector3::z(),
+            Vector3::new(1.0, -2.0, 3.0).normalize(),
+            Vector3::new(-0.3, 0.1, -0.9).normalize(),
+        ];
+        for n in normals.iter() {
+            let (e0, e1) = onb_from_normal(*n);
+            assert_close(e0.norm(), 1.0);
+            assert_close(e1.norm(), 1.0);
+            assert_close(e0.dot(&e1), 0.0);
+            assert_close(e0.dot(n), 0.0);
+            assert_close(e1.dot(n), 0.0);
+        }
+    }
+
+    #[test]
+    fn refract_ex_transmits_and_reflects() {
+        let n = Vector3::y();
+        let angle = std::f32::consts::FRAC_PI_4;
+        // into glass at 45°, bending towards the normal by Snell's law
+        let i = Vector3::new(f32::sin(angle), -f32::cos(angle), 0.0);
+        match refract_ex(i, n, 1.0, 1.5) {
+            Refraction::Transmit(dir) => {
+                assert_close(dir.norm(), 1.0);
+                assert_close(dir.x, f32::sin(angle) / 1.5);
+                assert!(dir.y < 0.0);
+            },
+            Refraction::TotalInternal => panic!("total reflection entering glass"),
+        }
+
+        // out of the same glass at 60°, past the critical angle of about 41.8°
+        let angle = std::f32::consts::FRAC_PI_3;
+        let i = Vector3::new(f32::sin(angle), f32::cos(angle), 0.0);
+        assert_eq!(refract_ex(i, n, 1.0, 1.5), Refraction::TotalInternal);
+        // while a steeper one at 30° still gets out
+        let angle = std::f32::consts::FRAC_PI_6;
+        let i = Vector3::new(f32::sin(angle), f32::cos(angle), 0.0);
+        assert!(matches!(refract_ex(i, n, 1.0, 1.5), Refraction::Transmit(_)));
     }
 }
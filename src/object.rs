@@ -1,7 +1,23 @@
-use nalgebra::Vector3;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod mesh;
+
+use nalgebra::{Matrix3, Point3, Similarity3, Vector3, U3};
 use crate::{
-    material::{Diffuse, DiffuseKind, Material, Refract, Specular},
-    math::{reflect, refract},
+    framebuffer::Framebuffer,
+    material::Material,
+    math::{
+        azimuth,
+        azimuth_tangent,
+        face_forward,
+        intersect_plane,
+        orthonormal_basis,
+        plane_coords,
+        solve_quartic,
+        sphere_uv,
+    },
+    sampling::Sampler,
+    RenderError,
 };
 
 #[derive(Debug, Clone)]
@@ -10,10 +26,74 @@ pub struct IntersectionInfo {
     pub hit: Vector3<f32>,
     pub normal: Vector3<f32>,
     pub material: Material,
+    pub uv: (f32, f32),
+    /// Unit direction of increasing `u` on the surface, for tangent space normal mapping.
+    pub tangent: Vector3<f32>,
 }
 
+/// Something a ray can hit.
+///
+/// Hits report a UV coordinate for texturing, with `(0, 0)` at the bottom left and `(1, 1)` at
+/// the top right of the texture. Bounded surfaces span the unit square exactly once; unbounded
+/// ones (planes, checkerboard cells) advance by one per world unit or cell and rely on textures
+/// wrapping around.
+///
+/// Ray directions passed in must be normalized, so that distances come out in world units.
+/// The scene normalizes each ray once before testing it against any object.
 pub trait Object: Sync {
     fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo>;
+
+    /// Like `ray_intersect`, for a ray traced at `time` within the shutter interval. Only
+    /// moving objects care, everything else is the same at all times.
+    fn ray_intersect_at(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        _time: f32,
+    ) -> Option<IntersectionInfo> {
+        self.ray_intersect(orig, dir)
+    }
+
+    /// Box enclosing the whole object, or `None` if it's unbounded. The box may be loose, and
+    /// its material is unused. Moving objects are enclosed over the whole of `[0, 1]`.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// Spans of the ray lying inside the object as `(enter, exit)` pairs, sorted and disjoint.
+    /// Spans entirely behind the origin are omitted, and a ray starting inside the object gets
+    /// an `enter` at negative `dist`.
+    ///
+    /// The default implementation is exact for closed convex objects with outward normals.
+    /// Flat objects are treated as having zero thickness.
+    fn ray_intervals(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+    ) -> Vec<(IntersectionInfo, IntersectionInfo)> {
+        let first = match self.ray_intersect(orig, dir) {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        if first.normal.dot(&dir).is_sign_positive() {
+            // leaving the object, so the origin is inside
+            let enter = IntersectionInfo {
+                dist: f32::NEG_INFINITY,
+                ..first.clone()
+            };
+            return vec![(enter, first)];
+        }
+
+        let step = 1e-4;
+        let exit = self
+            .ray_intersect(first.hit + dir * step, dir)
+            .map(|exit| IntersectionInfo {
+                dist: first.dist + step + exit.dist,
+                ..exit
+            })
+            .unwrap_or_else(|| first.clone());
+        vec![(first, exit)]
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -35,11 +115,8 @@ impl Sphere {
     fn material(&self) -> Material {
         self.material.clone()
     }
-}
 
-impl Object for Sphere {
-    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
-        let dir_1 = dir.normalize();
+    fn line_intersect(&self, orig: Vector3<f32>, dir_1: Vector3<f32>) -> Option<(f32, f32)> {
         let radius_sq = self.radius * self.radius;
 
         let vec_to_center = self.center - orig;
@@ -50,30 +127,62 @@ impl Object for Sphere {
         }
 
         let segment_len = f32::sqrt(radius_sq - dist_to_line);
-        let near = dir_len - segment_len;
-        let far = dir_len + segment_len;
+        Some((dir_len - segment_len, dir_len + segment_len))
+    }
+
+    fn info_at(&self, orig: Vector3<f32>, dir_1: Vector3<f32>, dist: f32) -> IntersectionInfo {
+        let hit = orig + dir_1 * dist;
+        let normal = (hit - self.center).normalize();
+        IntersectionInfo {
+            dist,
+            hit,
+            normal,
+            material: self.material(),
+            uv: sphere_uv(normal),
+            // `u` grows from +x towards +z
+            tangent: azimuth_tangent(-Vector3::y(), normal, normal),
+        }
+    }
+}
+
+impl Object for Sphere {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let (near, far) = self.line_intersect(orig, dir)?;
 
         let selected = if near.is_sign_negative() { far } else { near };
         if selected.is_sign_negative() {
             None
         } else {
-            let hit = orig + dir_1 * selected;
-            Some(IntersectionInfo {
-                dist: selected,
-                hit,
-                normal: (hit - self.center).normalize(),
-                material: self.material(),
-            })
+            Some(self.info_at(orig, dir, selected))
+        }
+    }
+
+    fn ray_intervals(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+    ) -> Vec<(IntersectionInfo, IntersectionInfo)> {
+        match self.line_intersect(orig, dir) {
+            Some((near, far)) if !far.is_sign_negative() => {
+                vec![(self.info_at(orig, dir, near), self.info_at(orig, dir, far))]
+            },
+            _ => Vec::new(),
         }
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent, Material::none()))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Checkerboard {
     origin: Vector3<f32>,
     cell_dir: (Vector3<f32>, Vector3<f32>),
-    dims: (u32, u32),
+    dims: Option<(u32, u32)>,
     material: (Material, Material),
+    double_sided: bool,
 }
 
 impl Checkerboard {
@@ -86,8 +195,33 @@ impl Checkerboard {
         Self {
             origin,
             cell_dir,
-            dims,
+            dims: Some(dims),
+            material,
+            double_sided: false,
+        }
+    }
+
+    /// A board tiling the whole plane, in both directions from `origin`.
+    pub fn infinite(
+        origin: Vector3<f32>,
+        cell_dir: (Vector3<f32>, Vector3<f32>),
+        material: (Material, Material),
+    ) -> Self {
+        Self {
+            origin,
+            cell_dir,
+            dims: None,
             material,
+            double_sided: false,
+        }
+    }
+
+    /// Reports the normal facing the incoming ray, so that the board shades the same from
+    /// either side.
+    pub fn with_double_sided(self) -> Self {
+        Self {
+            double_sided: true,
+            ..self
         }
     }
 
@@ -98,34 +232,1765 @@ impl Checkerboard {
 
 impl Object for Checkerboard {
     fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
-        let p = orig - self.origin;
         let n = self.normal();
-        let dir = dir.normalize();
-        let neg_dist = n.dot(&p) / n.dot(&dir);
-        if neg_dist.is_sign_positive() {
-            return None;
-        }
+        let dist = intersect_plane(orig, dir, self.origin, n)?;
 
-        let hit = p - neg_dist * dir;
-        let len_0 = hit.dot(&self.cell_dir.0) / self.cell_dir.0.dot(&self.cell_dir.0);
-        let len_1 = hit.dot(&self.cell_dir.1) / self.cell_dir.1.dot(&self.cell_dir.1);
-        if len_0 < 0.0 || len_1 < 0.0 || len_0 >= self.dims.0 as f32 || len_1 >= self.dims.1 as f32 {
-            return None;
+        let hit = orig + dist * dir;
+        let (len_0, len_1) = plane_coords(hit - self.origin, self.cell_dir);
+        if let Some(dims) = self.dims {
+            if len_0 < 0.0 || len_1 < 0.0 || len_0 >= dims.0 as f32 || len_1 >= dims.1 as f32 {
+                return None;
+            }
         }
-        let parity = len_0 as u32 + len_1 as u32;
+        // floor rather than truncate, so that cells stay aligned across the origin
+        let parity = f32::floor(len_0) as i64 + f32::floor(len_1) as i64;
 
-        let hit = hit + self.origin;
-        let material = if parity % 2 == 0 {
+        let material = if parity.rem_euclid(2) == 0 {
             self.material.0.clone()
         } else {
             self.material.1.clone()
         };
 
+        let normal = if self.double_sided { face_forward(n, dir) } else { n };
+        Some(IntersectionInfo {
+            dist,
+            hit,
+            normal,
+            material,
+            // position within the cell, so textures repeat once per cell
+            uv: (len_0 - f32::floor(len_0), len_1 - f32::floor(len_1)),
+            tangent: self.cell_dir.0.normalize(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (cols, rows) = self.dims?;
+        let (edge_0, edge_1) = (self.cell_dir.0 * cols as f32, self.cell_dir.1 * rows as f32);
+        Some(Aabb::from_points(&[
+            self.origin,
+            self.origin + edge_0,
+            self.origin + edge_1,
+            self.origin + edge_0 + edge_1,
+        ]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    vertices: [Vector3<f32>; 3],
+    material: Material,
+}
+
+impl Triangle {
+    pub fn new(v0: Vector3<f32>, v1: Vector3<f32>, v2: Vector3<f32>, material: Material) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            material,
+        }
+    }
+
+    fn normal(&self) -> Vector3<f32> {
+        let [v0, v1, v2] = self.vertices;
+        (v1 - v0).cross(&(v2 - v0)).normalize()
+    }
+}
+
+/// Möller–Trumbore intersection of a ray with normalized `dir`, returning `(t, u, v)` where `u`
+/// and `v` are the barycentric weights of `v1` and `v2`.
+fn intersect_triangle(
+    orig: Vector3<f32>,
+    dir: Vector3<f32>,
+    [v0, v1, v2]: [Vector3<f32>; 3],
+) -> Option<(f32, f32, f32)> {
+    let edge_1 = v1 - v0;
+    let edge_2 = v2 - v0;
+
+    let p = dir.cross(&edge_2);
+    let det = edge_1.dot(&p);
+    if det.abs() < 1e-8 {
+        // parallel to the triangle plane
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let s = orig - v0;
+    let u = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(&edge_1);
+    let v = dir.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(&q) * inv_det;
+    if t.is_sign_negative() {
+        return None;
+    }
+    Some((t, u, v))
+}
+
+impl Object for Triangle {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let (t, u, v) = intersect_triangle(orig, dir, self.vertices)?;
+        Some(IntersectionInfo {
+            dist: t,
+            hit: orig + dir * t,
+            normal: self.normal(),
+            material: self.material.clone(),
+            uv: (u, v),
+            tangent: (self.vertices[1] - self.vertices[0]).normalize(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    vertices: Vec<Vector3<f32>>,
+    faces: Vec<[usize; 3]>,
+    face_normals: Vec<Vector3<f32>>,
+    vertex_normals: Option<Vec<Vector3<f32>>>,
+    vertex_uvs: Option<Vec<(f32, f32)>>,
+    materials: Vec<Material>,
+    face_materials: Option<Vec<usize>>,
+}
+
+impl TriangleMesh {
+    pub fn new(vertices: Vec<Vector3<f32>>, faces: Vec<[usize; 3]>, material: Material) -> Self {
+        let face_normals = faces
+            .iter()
+            .map(|&[i0, i1, i2]| {
+                let v0 = vertices[i0];
+                (vertices[i1] - v0).cross(&(vertices[i2] - v0)).normalize()
+            })
+            .collect();
+        Self {
+            vertices,
+            faces,
+            face_normals,
+            vertex_normals: None,
+            vertex_uvs: None,
+            materials: vec![material],
+            face_materials: None,
+        }
+    }
+
+    /// Shades with normals interpolated from `normals`, one per vertex, instead of flat face
+    /// normals.
+    pub fn with_vertex_normals(self, normals: Vec<Vector3<f32>>) -> Self {
+        assert_eq!(normals.len(), self.vertices.len(), "one normal per vertex is required");
+        Self {
+            vertex_normals: Some(normals.into_iter().map(|n| n.normalize()).collect()),
+            ..self
+        }
+    }
+
+    /// Textures with coordinates interpolated from `uvs`, one per vertex, instead of the
+    /// barycentric coordinates of each face.
+    pub fn with_vertex_uvs(self, uvs: Vec<(f32, f32)>) -> Self {
+        assert_eq!(uvs.len(), self.vertices.len(), "one UV coordinate per vertex is required");
+        Self {
+            vertex_uvs: Some(uvs),
+            ..self
+        }
+    }
+
+    /// Shades each face with its own material, `face_materials[face]` indexing into
+    /// `materials`, instead of the single material of the mesh.
+    pub fn with_face_materials(self, materials: Vec<Material>, face_materials: Vec<usize>) -> Self {
+        assert_eq!(face_materials.len(), self.faces.len(), "one material index per face is required");
+        assert!(
+            face_materials.iter().all(|&i| i < materials.len()),
+            "material index out of range",
+        );
+        Self {
+            materials,
+            face_materials: Some(face_materials),
+            ..self
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vector3<f32>] {
+        &self.vertices
+    }
+
+    pub fn faces(&self) -> &[[usize; 3]] {
+        &self.faces
+    }
+
+    pub fn face_normals(&self) -> &[Vector3<f32>] {
+        &self.face_normals
+    }
+
+    fn face_material(&self, face: usize) -> &Material {
+        match &self.face_materials {
+            Some(indices) => &self.materials[indices[face]],
+            None => &self.materials[0],
+        }
+    }
+
+    fn face_vertices(&self, face: usize) -> [Vector3<f32>; 3] {
+        let [i0, i1, i2] = self.faces[face];
+        [self.vertices[i0], self.vertices[i1], self.vertices[i2]]
+    }
+
+    fn nearest_face(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<(usize, (f32, f32, f32))> {
+        (0..self.faces.len())
+            .filter_map(|face| {
+                intersect_triangle(orig, dir, self.face_vertices(face)).map(|hit| (face, hit))
+            })
+            .fold(None, |nearest: Option<(usize, (f32, f32, f32))>, (face, hit)| match nearest {
+                Some((_, (best, _, _))) if best <= hit.0 => nearest,
+                _ => Some((face, hit)),
+            })
+    }
+}
+
+impl Object for TriangleMesh {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let (face, (t, u, v)) = self.nearest_face(orig, dir)?;
+
+        let normal = match &self.vertex_normals {
+            Some(normals) => {
+                // same barycentrics as the intersection test, so the weights stay consistent
+                let [i0, i1, i2] = self.faces[face];
+                (normals[i0] * (1.0 - u - v) + normals[i1] * u + normals[i2] * v).normalize()
+            },
+            None => self.face_normals[face],
+        };
+        let uv = match &self.vertex_uvs {
+            Some(uvs) => {
+                let [i0, i1, i2] = self.faces[face];
+                let w0 = 1.0 - u - v;
+                (
+                    uvs[i0].0 * w0 + uvs[i1].0 * u + uvs[i2].0 * v,
+                    uvs[i0].1 * w0 + uvs[i1].1 * u + uvs[i2].1 * v,
+                )
+            },
+            None => (u, v),
+        };
+        let [v0, v1, _] = self.face_vertices(face);
+        Some(IntersectionInfo {
+            dist: t,
+            hit: orig + dir * t,
+            normal,
+            material: self.face_material(face).clone(),
+            uv,
+            tangent: (v1 - v0).normalize(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.vertices.is_empty() {
+            return None;
+        }
+        Some(Aabb::from_points(&self.vertices))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Plane {
+    origin: Vector3<f32>,
+    normal: Vector3<f32>,
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(origin: Vector3<f32>, normal: Vector3<f32>, material: Material) -> Self {
+        Self {
+            origin,
+            normal: normal.normalize(),
+            material,
+        }
+    }
+}
+
+impl Object for Plane {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let t = intersect_plane(orig, dir, self.origin, self.normal)?;
+
+        let hit = orig + dir * t;
+        let (e0, e1) = orthonormal_basis(self.normal);
+        let offset = hit - self.origin;
+        Some(IntersectionInfo {
+            dist: t,
+            hit,
+            // face the incoming ray, so the plane is lit from either side
+            normal: face_forward(self.normal, dir),
+            material: self.material.clone(),
+            uv: (offset.dot(&e0), offset.dot(&e1)),
+            tangent: e0,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Aabb {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    material: Material,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>, material: Material) -> Self {
+        Self {
+            min,
+            max,
+            material,
+        }
+    }
+
+    pub fn from_center(center: Vector3<f32>, half_extents: Vector3<f32>, material: Material) -> Self {
+        Self::new(center - half_extents, center + half_extents, material)
+    }
+
+    pub fn min(&self) -> Vector3<f32> {
+        self.min
+    }
+
+    pub fn max(&self) -> Vector3<f32> {
+        self.max
+    }
+
+    /// Smallest box containing all of `points`, which must not be empty.
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        let min = points.iter().fold(points[0], |acc, p| acc.zip_map(p, f32::min));
+        let max = points.iter().fold(points[0], |acc, p| acc.zip_map(p, f32::max));
+        Self::new(min, max, Material::none())
+    }
+
+    pub(crate) fn union(&self, other: &Aabb) -> Aabb {
+        let min = self.min.zip_map(&other.min, f32::min);
+        let max = self.max.zip_map(&other.max, f32::max);
+        Aabb::new(min, max, Material::none())
+    }
+
+    /// Overlap of the two boxes; disjoint boxes give an empty box with `min > max`.
+    pub(crate) fn intersection(&self, other: &Aabb) -> Aabb {
+        let min = self.min.zip_map(&other.min, f32::max);
+        let max = self.max.zip_map(&other.max, f32::min);
+        Aabb::new(min, max, Material::none())
+    }
+
+    pub(crate) fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    /// Entry and exit ray parameters of the slabs, which may be negative.
+    pub(crate) fn hit_range(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<(f32, f32)> {
+        let mut t_near = f32::NEG_INFINITY;
+        let mut t_far = f32::INFINITY;
+        for axis in 0..3 {
+            if dir[axis] == 0.0 {
+                if orig[axis] < self.min[axis] || orig[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inv = 1.0 / dir[axis];
+            let t_0 = (self.min[axis] - orig[axis]) * inv;
+            let t_1 = (self.max[axis] - orig[axis]) * inv;
+            t_near = f32::max(t_near, f32::min(t_0, t_1));
+            t_far = f32::min(t_far, f32::max(t_0, t_1));
+        }
+        if t_near > t_far { None } else { Some((t_near, t_far)) }
+    }
+}
+
+impl Object for Aabb {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        // (t, axis, sign of the face normal)
+        let mut t_near = (f32::NEG_INFINITY, 0, 0.0);
+        let mut t_far = (f32::INFINITY, 0, 0.0);
+        for axis in 0..3 {
+            let (o, d) = (orig[axis], dir[axis]);
+            let (lo, hi) = (self.min[axis], self.max[axis]);
+            if d == 0.0 {
+                // parallel to the slab; the interval is either empty or infinite
+                if o < lo || o > hi {
+                    return None;
+                }
+                continue;
+            }
+
+            let inv = 1.0 / d;
+            let (t_lo, t_hi) = ((lo - o) * inv, (hi - o) * inv);
+            let (enter, exit) = if inv.is_sign_negative() {
+                ((t_hi, 1.0), (t_lo, -1.0))
+            } else {
+                ((t_lo, -1.0), (t_hi, 1.0))
+            };
+            if enter.0 > t_near.0 {
+                t_near = (enter.0, axis, enter.1);
+            }
+            if exit.0 < t_far.0 {
+                t_far = (exit.0, axis, exit.1);
+            }
+        }
+
+        if t_near.0 > t_far.0 || t_far.0.is_sign_negative() {
+            return None;
+        }
+        // starting inside the box, so the exit face is the visible one
+        let (t, axis, sign) = if t_near.0.is_sign_negative() { t_far } else { t_near };
+
+        let mut normal = Vector3::zeros();
+        normal[axis] = sign;
+        let hit = orig + dir * t;
+        // the other two axes in cyclic order, across the face
+        let rel = (hit - self.min).component_div(&(self.max - self.min));
+        let mut tangent = Vector3::zeros();
+        tangent[(axis + 1) % 3] = 1.0;
         Some(IntersectionInfo {
-            dist: -neg_dist,
+            dist: t,
             hit,
-            normal: n,
+            normal,
+            material: self.material.clone(),
+            uv: (rel[(axis + 1) % 3], rel[(axis + 2) % 3]),
+            tangent,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max, Material::none()))
+    }
+}
+
+/// A box of uniform fog, added with `Scene::push_volume`. Rays crossing it scatter after an
+/// exponentially distributed distance, `1 / density` on average, and see `color` when they do.
+#[derive(Debug, Clone)]
+pub struct FogVolume {
+    bounds: Aabb,
+    density: f32,
+    color: [f32; 3],
+}
+
+impl FogVolume {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>, density: f32, color: [f32; 3]) -> Self {
+        Self {
+            bounds: Aabb::new(min, max, Material::none()),
+            density,
+            color,
+        }
+    }
+
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    /// Where a ray with normalized `dir` scatters in the fog before reaching `max_dist`, if it
+    /// does.
+    pub(crate) fn scatter_distance(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        max_dist: f32,
+        sampler: &mut Sampler,
+    ) -> Option<f32> {
+        let (enter, exit) = self.bounds.hit_range(orig, dir)?;
+        let enter = f32::max(enter, 0.0);
+        let exit = f32::min(exit, max_dist);
+        if enter >= exit || self.density <= 0.0 {
+            return None;
+        }
+        let free_path = -f32::ln(1.0 - sampler.next_f32()) / self.density;
+        let dist = enter + free_path;
+        if dist < exit { Some(dist) } else { None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    base: Vector3<f32>,
+    axis: Vector3<f32>,
+    radius: f32,
+    height: f32,
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vector3<f32>,
+        axis: Vector3<f32>,
+        radius: f32,
+        height: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            base,
+            axis: axis.normalize(),
+            radius,
+            height,
             material,
+        }
+    }
+}
+
+impl Object for Cylinder {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let oc = orig - self.base;
+        let oc_axis = oc.dot(&self.axis);
+        let dir_axis = dir.dot(&self.axis);
+
+        let mut nearest: Option<(f32, Vector3<f32>)> = None;
+        let mut consider = |t: f32, normal: Vector3<f32>| {
+            if t.is_sign_negative() {
+                return;
+            }
+            match nearest {
+                Some((best, _)) if best <= t => {},
+                _ => nearest = Some((t, normal)),
+            }
+        };
+
+        // side: components perpendicular to the axis form a 2D circle test
+        let dir_perp = dir - self.axis * dir_axis;
+        let oc_perp = oc - self.axis * oc_axis;
+        let a = dir_perp.dot(&dir_perp);
+        if a > 1e-8 {
+            let b = dir_perp.dot(&oc_perp);
+            let c = oc_perp.dot(&oc_perp) - self.radius * self.radius;
+            let disc = b * b - a * c;
+            // a grazing ray has disc == 0, where both roots coincide
+            if !disc.is_sign_negative() {
+                let disc_sqrt = f32::sqrt(disc);
+                for &t in &[(-b - disc_sqrt) / a, (-b + disc_sqrt) / a] {
+                    let h = oc_axis + t * dir_axis;
+                    if h >= 0.0 && h <= self.height {
+                        let normal = (oc_perp + dir_perp * t) / self.radius;
+                        consider(t, normal);
+                    }
+                }
+            }
+        }
+
+        // end caps
+        if dir_axis.abs() > 1e-8 {
+            let radius_sq = self.radius * self.radius;
+            for &(h, normal) in &[(0.0, -self.axis), (self.height, self.axis)] {
+                let t = (h - oc_axis) / dir_axis;
+                let p_perp = oc_perp + dir_perp * t;
+                if p_perp.dot(&p_perp) <= radius_sq {
+                    consider(t, normal);
+                }
+            }
+        }
+
+        let basis = orthonormal_basis(self.axis);
+        nearest.map(|(t, normal)| {
+            let hit = orig + dir * t;
+            let p = hit - self.base;
+            let h = p.dot(&self.axis);
+            let (uv, tangent) = if normal.dot(&self.axis).abs() > 0.5 {
+                // caps map the disk onto the unit square
+                let scale = 2.0 * self.radius;
+                ((0.5 + p.dot(&basis.0) / scale, 0.5 + p.dot(&basis.1) / scale), basis.0)
+            } else {
+                ((azimuth(p, basis), h / self.height), azimuth_tangent(self.axis, p, normal))
+            };
+            IntersectionInfo {
+                dist: t,
+                hit,
+                normal: normal.normalize(),
+                material: self.material.clone(),
+                uv,
+                tangent,
+            }
         })
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.radius);
+        let top = self.base + self.axis * self.height;
+        Some(Aabb::from_points(&[self.base - extent, self.base + extent, top - extent, top + extent]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cone {
+    apex: Vector3<f32>,
+    axis: Vector3<f32>,
+    half_angle: f32,
+    height: f32,
+    capped: bool,
+    material: Material,
+}
+
+impl Cone {
+    pub fn new(
+        apex: Vector3<f32>,
+        axis: Vector3<f32>,
+        half_angle: f32,
+        height: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            apex,
+            axis: axis.normalize(),
+            half_angle,
+            height,
+            capped: false,
+            material,
+        }
+    }
+
+    pub fn with_cap(self) -> Self {
+        Self {
+            capped: true,
+            ..self
+        }
+    }
+}
+
+impl Object for Cone {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let co = orig - self.apex;
+        let cos_sq = f32::powi(f32::cos(self.half_angle), 2);
+        let dir_axis = dir.dot(&self.axis);
+        let co_axis = co.dot(&self.axis);
+
+        let mut nearest: Option<(f32, Vector3<f32>)> = None;
+        let mut consider = |t: f32, normal: Vector3<f32>| {
+            if t.is_sign_negative() {
+                return;
+            }
+            match nearest {
+                Some((best, _)) if best <= t => {},
+                _ => nearest = Some((t, normal)),
+            }
+        };
+
+        // side: (p·axis)² = cos²θ |p|², restricted to the nappe 0 <= p·axis <= height
+        let a = dir_axis * dir_axis - cos_sq;
+        let b = dir_axis * co_axis - cos_sq * dir.dot(&co);
+        let c = co_axis * co_axis - cos_sq * co.dot(&co);
+        let roots = if a.abs() < 1e-8 {
+            // parallel to a generating line, only one crossing
+            if b.abs() < 1e-8 { vec![] } else { vec![-c / (2.0 * b)] }
+        } else {
+            let disc = b * b - a * c;
+            if disc.is_sign_negative() {
+                vec![]
+            } else {
+                let disc_sqrt = f32::sqrt(disc);
+                vec![(-b - disc_sqrt) / a, (-b + disc_sqrt) / a]
+            }
+        };
+        for t in roots {
+            let p = co + dir * t;
+            let h = p.dot(&self.axis);
+            if h >= 0.0 && h <= self.height {
+                // negated gradient of (p·axis)² - cos²θ |p|², pointing away from the axis
+                let normal = p * cos_sq - self.axis * h;
+                consider(t, normal);
+            }
+        }
+
+        if self.capped && dir_axis.abs() > 1e-8 {
+            let t = (self.height - co_axis) / dir_axis;
+            let p = co + dir * t;
+            let cap_radius = self.height * f32::tan(self.half_angle);
+            let p_perp = p - self.axis * self.height;
+            if p_perp.dot(&p_perp) <= cap_radius * cap_radius {
+                consider(t, self.axis);
+            }
+        }
+
+        let basis = orthonormal_basis(self.axis);
+        nearest.map(|(t, normal)| {
+            let hit = orig + dir * t;
+            let p = hit - self.apex;
+            let h = p.dot(&self.axis);
+            let (uv, tangent) = if normal == self.axis {
+                // the cap maps its disk onto the unit square
+                let scale = 2.0 * self.height * f32::tan(self.half_angle);
+                ((0.5 + p.dot(&basis.0) / scale, 0.5 + p.dot(&basis.1) / scale), basis.0)
+            } else {
+                ((azimuth(p, basis), h / self.height), azimuth_tangent(self.axis, p, normal))
+            };
+            IntersectionInfo {
+                dist: t,
+                hit,
+                normal: normal.normalize(),
+                material: self.material.clone(),
+                uv,
+                tangent,
+            }
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.height * f32::tan(self.half_angle));
+        let base = self.apex + self.axis * self.height;
+        Some(Aabb::from_points(&[self.apex, base - extent, base + extent]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Torus {
+    center: Vector3<f32>,
+    axis: Vector3<f32>,
+    major_radius: f32,
+    minor_radius: f32,
+    material: Material,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector3<f32>,
+        axis: Vector3<f32>,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            center,
+            axis: axis.normalize(),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Object for Torus {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        // Points q on the torus satisfy
+        //     (|q|² + R² - r²)² = 4R² (|q|² - (q·axis)²)
+        // relative to the center. Substituting q = p + t d with |d| = 1 gives
+        //     |q|²     = t² + 2 (p·d) t + p·p
+        //     q·axis   = (d·axis) t + p·axis
+        // and expanding both sides leaves a monic quartic in t.
+        let p = (orig - self.center).map(f64::from);
+        let d = dir.map(f64::from);
+        let axis = self.axis.map(f64::from);
+        let major_sq = f64::from(self.major_radius * self.major_radius);
+        let minor_sq = f64::from(self.minor_radius * self.minor_radius);
+
+        let m = p.dot(&p);
+        let n = p.dot(&d);
+        let k = m + major_sq - minor_sq;
+        let d_axis = d.dot(&axis);
+        let p_axis = p.dot(&axis);
+
+        // left side:  (t² + 2n t + k)²
+        // right side: 4R² ((1 - (d·axis)²) t² + 2 (n - (d·axis)(p·axis)) t + m - (p·axis)²)
+        let four_major_sq = 4.0 * major_sq;
+        let c3 = 4.0 * n;
+        let c2 = 4.0 * n * n + 2.0 * k - four_major_sq * (1.0 - d_axis * d_axis);
+        let c1 = 4.0 * n * k - 2.0 * four_major_sq * (n - d_axis * p_axis);
+        let c0 = k * k - four_major_sq * (m - p_axis * p_axis);
+
+        let t = solve_quartic(c3, c2, c1, c0)
+            .into_iter()
+            .filter(|&t| t > 1e-4)
+            .fold(None, |nearest: Option<f64>, t| match nearest {
+                Some(best) if best <= t => Some(best),
+                _ => Some(t),
+            })? as f32;
+
+        let hit = orig + dir * t;
+        // gradient of the implicit function at the hit
+        let q = hit - self.center;
+        let q_sq = q.dot(&q);
+        let sum = q_sq + self.major_radius * self.major_radius - self.minor_radius * self.minor_radius;
+        let radial = q - self.axis * q.dot(&self.axis);
+        let normal = q * (4.0 * sum) - radial * (8.0 * self.major_radius * self.major_radius);
+        // around the main axis, then around the tube with its outer equator at v = 0.5
+        let basis = orthonormal_basis(self.axis);
+        let radial_dir = radial.normalize();
+        let tube = q - radial_dir * self.major_radius;
+        Some(IntersectionInfo {
+            dist: t,
+            hit,
+            normal: normal.normalize(),
+            material: self.material.clone(),
+            uv: (azimuth(q, basis), azimuth(tube, (radial_dir, self.axis))),
+            tangent: azimuth_tangent(self.axis, q, normal),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.major_radius + self.minor_radius);
+        Some(Aabb::new(self.center - extent, self.center + extent, Material::none()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Disk {
+    center: Vector3<f32>,
+    normal: Vector3<f32>,
+    radius: f32,
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Vector3<f32>, normal: Vector3<f32>, radius: f32, material: Material) -> Self {
+        Self {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl Object for Disk {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let t = intersect_plane(orig, dir, self.center, self.normal)?;
+
+        let hit = orig + dir * t;
+        let radial = hit - self.center;
+        if radial.dot(&radial) > self.radius * self.radius {
+            return None;
+        }
+
+        let (e0, e1) = orthonormal_basis(self.normal);
+        let scale = 2.0 * self.radius;
+        Some(IntersectionInfo {
+            dist: t,
+            hit,
+            normal: face_forward(self.normal, dir),
+            material: self.material.clone(),
+            uv: (0.5 + radial.dot(&e0) / scale, 0.5 + radial.dot(&e1) / scale),
+            tangent: e0,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent, Material::none()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ellipsoid {
+    center: Vector3<f32>,
+    radii: Vector3<f32>,
+    material: Material,
+}
+
+impl Ellipsoid {
+    pub fn new(center: Vector3<f32>, radii: Vector3<f32>, material: Material) -> Self {
+        Self {
+            center,
+            radii,
+            material,
+        }
+    }
+}
+
+impl Object for Ellipsoid {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        // in unit sphere space; the scaled direction is intentionally left unnormalized so that
+        // `t` stays a distance along the world space ray
+        let o = (orig - self.center).component_div(&self.radii);
+        let d = dir.component_div(&self.radii);
+
+        let a = d.dot(&d);
+        let b = o.dot(&d);
+        let c = o.dot(&o) - 1.0;
+        let disc = b * b - a * c;
+        if disc.is_sign_negative() {
+            return None;
+        }
+
+        let disc_sqrt = f32::sqrt(disc);
+        let near = (-b - disc_sqrt) / a;
+        let far = (-b + disc_sqrt) / a;
+        let selected = if near.is_sign_negative() { far } else { near };
+        if selected.is_sign_negative() {
+            return None;
+        }
+
+        let hit = orig + dir * selected;
+        // the unit sphere normal is `o + d t`; the inverse transpose of the scale divides by
+        // the radii once more
+        let unit = o + d * selected;
+        let normal = unit.component_div(&self.radii).normalize();
+        Some(IntersectionInfo {
+            dist: selected,
+            hit,
+            normal,
+            material: self.material.clone(),
+            uv: sphere_uv(unit.normalize()),
+            // stretched along with the sphere
+            tangent: azimuth_tangent(-Vector3::y(), unit, unit)
+                .component_mul(&self.radii)
+                .normalize(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = self.radii.map(f32::abs);
+        Some(Aabb::new(self.center - extent, self.center + extent, Material::none()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+
+    fn bounding_box(self, a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+        match self {
+            CsgOp::Union => Some(a?.union(&b?)),
+            // the result lies within either operand, an unbounded one doesn't constrain it
+            CsgOp::Intersection => match (a, b) {
+                (Some(a), Some(b)) => Some(a.intersection(&b)),
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => a,
+        }
+    }
+}
+
+fn csg_intervals(
+    op: CsgOp,
+    a: &dyn Object,
+    b: &dyn Object,
+    orig: Vector3<f32>,
+    dir: Vector3<f32>,
+) -> Vec<(IntersectionInfo, IntersectionInfo)> {
+    // (info, from a, entering)
+    let mut events = Vec::new();
+    for (from_a, object) in [(true, a), (false, b)].iter() {
+        for (enter, exit) in object.ray_intervals(orig, dir) {
+            events.push((enter, *from_a, true));
+            events.push((exit, *from_a, false));
+        }
+    }
+    events.sort_by(|x, y| x.0.dist.partial_cmp(&y.0.dist).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut intervals = Vec::new();
+    let mut enter = None;
+    let (mut in_a, mut in_b) = (false, false);
+    for (mut info, from_a, entering) in events {
+        let was_inside = op.inside(in_a, in_b);
+        if from_a {
+            in_a = entering;
+        } else {
+            in_b = entering;
+        }
+        let is_inside = op.inside(in_a, in_b);
+        if was_inside == is_inside {
+            continue;
+        }
+
+        if let CsgOp::Difference = op {
+            if !from_a {
+                // carved out by b, so the surface faces into b
+                info.normal = -info.normal;
+            }
+        }
+        if is_inside {
+            enter = Some(info);
+        } else if let Some(enter) = enter.take() {
+            intervals.push((enter, info));
+        }
+    }
+    intervals
+}
+
+fn nearest_boundary(intervals: Vec<(IntersectionInfo, IntersectionInfo)>) -> Option<IntersectionInfo> {
+    intervals
+        .into_iter()
+        .flat_map(|(enter, exit)| vec![enter, exit])
+        .find(|info| !info.dist.is_sign_negative())
+}
+
+macro_rules! csg_object {
+    ($name:ident, $op:expr) => {
+        pub struct $name {
+            a: Box<dyn Object + Sync>,
+            b: Box<dyn Object + Sync>,
+        }
+
+        impl $name {
+            pub fn new<A: Object + 'static, B: Object + 'static>(a: A, b: B) -> Self {
+                Self {
+                    a: Box::new(a),
+                    b: Box::new(b),
+                }
+            }
+        }
+
+        impl Object for $name {
+            fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+                nearest_boundary(self.ray_intervals(orig, dir))
+            }
+
+            fn ray_intervals(
+                &self,
+                orig: Vector3<f32>,
+                dir: Vector3<f32>,
+            ) -> Vec<(IntersectionInfo, IntersectionInfo)> {
+                csg_intervals($op, &*self.a, &*self.b, orig, dir)
+            }
+
+            fn bounding_box(&self) -> Option<Aabb> {
+                $op.bounding_box(self.a.bounding_box(), self.b.bounding_box())
+            }
+        }
+    };
+}
+
+csg_object!(Union, CsgOp::Union);
+csg_object!(Intersection, CsgOp::Intersection);
+csg_object!(Difference, CsgOp::Difference);
+
+pub struct Transformed<T: Object> {
+    object: T,
+    transform: Similarity3<f32>,
+    inverse: Similarity3<f32>,
+    normal_matrix: Matrix3<f32>,
+}
+
+impl<T: Object> Transformed<T> {
+    pub fn new(object: T, transform: Similarity3<f32>) -> Self {
+        let inverse = transform.inverse();
+        // normals transform by the inverse transpose of the linear part
+        let normal_matrix = inverse
+            .to_homogeneous()
+            .fixed_slice::<U3, U3>(0, 0)
+            .transpose();
+        Self {
+            object,
+            transform,
+            inverse,
+            normal_matrix,
+        }
+    }
+
+    /// `dir` in object space, scaled back up to unit length.
+    fn local_dir(&self, dir: Vector3<f32>) -> Vector3<f32> {
+        self.inverse * dir * self.transform.scaling()
+    }
+
+    fn to_world(&self, info: IntersectionInfo) -> IntersectionInfo {
+        IntersectionInfo {
+            // the object space ray is normalized, so distances stretch by the scale factor
+            dist: info.dist * self.transform.scaling(),
+            hit: (self.transform * Point3::from(info.hit)).coords,
+            normal: (self.normal_matrix * info.normal).normalize(),
+            tangent: (self.transform * info.tangent).normalize(),
+            ..info
+        }
+    }
+}
+
+impl<T: Object> Object for Transformed<T> {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let local_orig = (self.inverse * Point3::from(orig)).coords;
+        let local_dir = self.local_dir(dir);
+        self.object
+            .ray_intersect(local_orig, local_dir)
+            .map(|info| self.to_world(info))
+    }
+
+    fn ray_intersect_at(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        time: f32,
+    ) -> Option<IntersectionInfo> {
+        let local_orig = (self.inverse * Point3::from(orig)).coords;
+        let local_dir = self.local_dir(dir);
+        self.object
+            .ray_intersect_at(local_orig, local_dir, time)
+            .map(|info| self.to_world(info))
+    }
+
+    fn ray_intervals(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+    ) -> Vec<(IntersectionInfo, IntersectionInfo)> {
+        let local_orig = (self.inverse * Point3::from(orig)).coords;
+        let local_dir = self.local_dir(dir);
+        self.object
+            .ray_intervals(local_orig, local_dir)
+            .into_iter()
+            .map(|(enter, exit)| (self.to_world(enter), self.to_world(exit)))
+            .collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let local = self.object.bounding_box()?;
+        let (min, max) = (local.min(), local.max());
+        let corners: Vec<_> = (0..8)
+            .map(|corner| {
+                let pick = |axis: usize| if corner & (1 << axis) == 0 { min[axis] } else { max[axis] };
+                let point = Point3::new(pick(0), pick(1), pick(2));
+                (self.transform * point).coords
+            })
+            .collect();
+        Some(Aabb::from_points(&corners))
+    }
+}
+
+/// An object travelling at a constant `velocity`, in world units per unit of time, for motion
+/// blur. It sits at its original position at time 0, which is also where rays without a time
+/// see it.
+pub struct Moving<T: Object> {
+    object: T,
+    velocity: Vector3<f32>,
+}
+
+impl<T: Object> Moving<T> {
+    pub fn new(object: T, velocity: Vector3<f32>) -> Self {
+        Self { object, velocity }
+    }
+}
+
+impl<T: Object> Object for Moving<T> {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        self.object.ray_intersect(orig, dir)
+    }
+
+    fn ray_intersect_at(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        time: f32,
+    ) -> Option<IntersectionInfo> {
+        // move the ray the other way instead of the object
+        let offset = self.velocity * time;
+        self.object
+            .ray_intersect_at(orig - offset, dir, time)
+            .map(|info| IntersectionInfo { hit: info.hit + offset, ..info })
+    }
+
+    fn ray_intervals(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+    ) -> Vec<(IntersectionInfo, IntersectionInfo)> {
+        self.object.ray_intervals(orig, dir)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let start = self.object.bounding_box()?;
+        let end = Aabb::new(start.min + self.velocity, start.max + self.velocity, Material::none());
+        Some(start.union(&end))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Capsule {
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    radius: f32,
+    material: Material,
+}
+
+impl Capsule {
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>, radius: f32, material: Material) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Object for Capsule {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let ab = self.b - self.a;
+        let len = ab.norm();
+        let axis = ab / len;
+        let radius_sq = self.radius * self.radius;
+
+        let mut nearest: Option<(f32, Vector3<f32>)> = None;
+        let mut consider = |t: f32, normal: Vector3<f32>| {
+            if t.is_sign_negative() {
+                return;
+            }
+            match nearest {
+                Some((best, _)) if best <= t => {},
+                _ => nearest = Some((t, normal)),
+            }
+        };
+
+        // body, the finite cylinder between the endpoints
+        let ao = orig - self.a;
+        let ao_axis = ao.dot(&axis);
+        let dir_axis = dir.dot(&axis);
+        let dir_perp = dir - axis * dir_axis;
+        let ao_perp = ao - axis * ao_axis;
+        let a = dir_perp.dot(&dir_perp);
+        if a > 1e-8 {
+            let b = dir_perp.dot(&ao_perp);
+            let c = ao_perp.dot(&ao_perp) - radius_sq;
+            let disc = b * b - a * c;
+            if !disc.is_sign_negative() {
+                let disc_sqrt = f32::sqrt(disc);
+                for &t in &[(-b - disc_sqrt) / a, (-b + disc_sqrt) / a] {
+                    let h = ao_axis + t * dir_axis;
+                    if h >= 0.0 && h <= len {
+                        consider(t, ao_perp + dir_perp * t);
+                    }
+                }
+            }
+        }
+
+        // hemispherical caps, each only on its own side of the body; both meet the body at the
+        // end circles with the same radial normal, so the seam is continuous
+        for &(center, outside) in &[(self.a, -1.0f32), (self.b, 1.0)] {
+            let oc = orig - center;
+            let b = oc.dot(&dir);
+            let c = oc.dot(&oc) - radius_sq;
+            let disc = b * b - c;
+            if disc.is_sign_negative() {
+                continue;
+            }
+            let disc_sqrt = f32::sqrt(disc);
+            for &t in &[-b - disc_sqrt, -b + disc_sqrt] {
+                let offset = oc + dir * t;
+                if (offset.dot(&axis) * outside).is_sign_positive() {
+                    consider(t, offset);
+                }
+            }
+        }
+
+        // v runs along the axis from the tip of one cap to the tip of the other
+        let basis = orthonormal_basis(axis);
+        nearest.map(|(t, normal)| {
+            let hit = orig + dir * t;
+            let p = hit - self.a;
+            let v = (p.dot(&axis) + self.radius) / (len + 2.0 * self.radius);
+            IntersectionInfo {
+                dist: t,
+                hit,
+                normal: normal.normalize(),
+                material: self.material.clone(),
+                uv: (azimuth(p, basis), v),
+                tangent: azimuth_tangent(axis, p, normal),
+            }
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vector3::repeat(self.radius);
+        Some(Aabb::from_points(&[self.a - extent, self.a + extent, self.b - extent, self.b + extent]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Quad {
+    corner: Vector3<f32>,
+    edges: (Vector3<f32>, Vector3<f32>),
+    material: Material,
+}
+
+impl Quad {
+    /// A rectangle spanned by two perpendicular `edges` starting from `corner`.
+    pub fn new(corner: Vector3<f32>, edges: (Vector3<f32>, Vector3<f32>), material: Material) -> Self {
+        Self {
+            corner,
+            edges,
+            material,
+        }
+    }
+
+    fn normal(&self) -> Vector3<f32> {
+        self.edges.0.cross(&self.edges.1).normalize()
+    }
+}
+
+impl Object for Quad {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let n = self.normal();
+        let t = intersect_plane(orig, dir, self.corner, n)?;
+
+        let hit = orig + dir * t;
+        let (u, v) = plane_coords(hit - self.corner, self.edges);
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        Some(IntersectionInfo {
+            dist: t,
+            hit,
+            normal: face_forward(n, dir),
+            material: self.material.clone(),
+            uv: (u, v),
+            tangent: self.edges.0.normalize(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (edge_0, edge_1) = self.edges;
+        Some(Aabb::from_points(&[
+            self.corner,
+            self.corner + edge_0,
+            self.corner + edge_1,
+            self.corner + edge_0 + edge_1,
+        ]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeightField {
+    origin: Vector3<f32>,
+    cell_size: f32,
+    dims: (usize, usize),
+    heights: Vec<f32>,
+    normals: Vec<Vector3<f32>>,
+    bounds: Aabb,
+    material: Material,
+}
+
+impl HeightField {
+    /// A terrain over the xz plane from `origin`, with `dims.0` samples along x and `dims.1`
+    /// samples along z in row-major order, spaced `cell_size` apart.
+    pub fn new(
+        origin: Vector3<f32>,
+        cell_size: f32,
+        dims: (usize, usize),
+        heights: Vec<f32>,
+        material: Material,
+    ) -> Self {
+        assert!(dims.0 >= 2 && dims.1 >= 2, "height field needs at least 2x2 samples");
+        assert_eq!(heights.len(), dims.0 * dims.1, "height count doesn't match dimensions");
+
+        let (cols, rows) = dims;
+        let at = |x: usize, z: usize| heights[z * cols + x];
+        // central differences, one-sided at the edges
+        let normals = (0..rows)
+            .flat_map(|z| (0..cols).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let (x0, x1) = (x.saturating_sub(1), usize::min(x + 1, cols - 1));
+                let (z0, z1) = (z.saturating_sub(1), usize::min(z + 1, rows - 1));
+                let dh_dx = (at(x1, z) - at(x0, z)) / ((x1 - x0) as f32 * cell_size);
+                let dh_dz = (at(x, z1) - at(x, z0)) / ((z1 - z0) as f32 * cell_size);
+                Vector3::from([-dh_dx, 1.0, -dh_dz]).normalize()
+            })
+            .collect();
+
+        let min_height = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max_height = heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let extent = Vector3::from([(cols - 1) as f32 * cell_size, 0.0, (rows - 1) as f32 * cell_size]);
+        let bounds = Aabb::new(
+            origin + Vector3::from([0.0, min_height, 0.0]),
+            origin + extent + Vector3::from([0.0, max_height, 0.0]),
+            Material::none(),
+        );
+
+        Self {
+            origin,
+            cell_size,
+            dims,
+            heights,
+            normals,
+            bounds,
+            material,
+        }
+    }
+
+    /// Builds the terrain from a grayscale PNG, one sample per pixel, mapping black to
+    /// `height_range.0` and white to `height_range.1`.
+    pub fn from_png<R: std::io::Read>(
+        r: R,
+        origin: Vector3<f32>,
+        cell_size: f32,
+        height_range: (f32, f32),
+        material: Material,
+    ) -> Result<Self, RenderError> {
+        let image = Framebuffer::read_png(r)?;
+        let (low, high) = height_range;
+        let heights = image
+            .buf()
+            .iter()
+            .map(|&[r, g, b]| low + (high - low) * (r + g + b) / 3.0)
+            .collect();
+        Ok(Self::new(origin, cell_size, (image.width(), image.height()), heights, material))
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Vector3<f32> {
+        let height = self.heights[z * self.dims.0 + x];
+        self.origin + Vector3::from([x as f32 * self.cell_size, height, z as f32 * self.cell_size])
+    }
+
+    fn intersect_cell(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        x: usize,
+        z: usize,
+    ) -> Option<(f32, Vector3<f32>)> {
+        let corners = [(x, z), (x, z + 1), (x + 1, z + 1), (x + 1, z)];
+        // two triangles sharing the (x, z)-(x+1, z+1) diagonal, both wound to face +y
+        [[0, 1, 2], [0, 2, 3]]
+            .iter()
+            .filter_map(|tri| {
+                let [a, b, c] = [corners[tri[0]], corners[tri[1]], corners[tri[2]]];
+                let vertices = [self.vertex(a.0, a.1), self.vertex(b.0, b.1), self.vertex(c.0, c.1)];
+                let (t, u, v) = intersect_triangle(orig, dir, vertices)?;
+                let normal_at = |(x, z): (usize, usize)| self.normals[z * self.dims.0 + x];
+                let normal = normal_at(a) * (1.0 - u - v) + normal_at(b) * u + normal_at(c) * v;
+                Some((t, normal.normalize()))
+            })
+            .fold(None, |nearest: Option<(f32, Vector3<f32>)>, hit| match nearest {
+                Some((best, _)) if best <= hit.0 => nearest,
+                _ => Some(hit),
+            })
+    }
+}
+
+impl Object for HeightField {
+    fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+        let (t_enter, t_exit) = self.bounds.hit_range(orig, dir)?;
+        if t_exit.is_sign_negative() {
+            return None;
+        }
+
+        // walk the cells under the ray in order (2D DDA over the xz grid), so the first cell
+        // with a hit holds the nearest one
+        let (cols, rows) = self.dims;
+        let start = orig + dir * f32::max(t_enter, 0.0) - self.origin;
+        let cell_of = |coord: f32, count: usize| {
+            f32::max(0.0, f32::min((count - 2) as f32, f32::floor(coord / self.cell_size))) as usize
+        };
+        let (mut x, mut z) = (cell_of(start.x, cols), cell_of(start.z, rows));
+
+        let axis_setup = |cell: usize, o: f32, d: f32| {
+            if d == 0.0 {
+                (0isize, f32::INFINITY, f32::INFINITY)
+            } else {
+                let step = if d > 0.0 { 1 } else { -1 };
+                let boundary = (cell as f32 + if d > 0.0 { 1.0 } else { 0.0 }) * self.cell_size;
+                (step, (boundary - o) / d, self.cell_size / d.abs())
+            }
+        };
+        let local_orig = orig - self.origin;
+        let (step_x, mut t_next_x, delta_x) = axis_setup(x, local_orig.x, dir.x);
+        let (step_z, mut t_next_z, delta_z) = axis_setup(z, local_orig.z, dir.z);
+
+        loop {
+            if let Some((t, normal)) = self.intersect_cell(orig, dir, x, z) {
+                let hit = orig + dir * t;
+                let local = hit - self.origin;
+                let extent = (
+                    (cols - 1) as f32 * self.cell_size,
+                    (rows - 1) as f32 * self.cell_size,
+                );
+                return Some(IntersectionInfo {
+                    dist: t,
+                    hit,
+                    normal,
+                    material: self.material.clone(),
+                    uv: (local.x / extent.0, local.z / extent.1),
+                    tangent: Vector3::x(),
+                });
+            }
+
+            if f32::min(t_next_x, t_next_z) > t_exit {
+                return None;
+            }
+            let (next_x, next_z) = if t_next_x < t_next_z {
+                t_next_x += delta_x;
+                (x as isize + step_x, z as isize)
+            } else {
+                t_next_z += delta_z;
+                (x as isize, z as isize + step_z)
+            };
+            if next_x < 0 || next_z < 0 || next_x as usize >= cols - 1 || next_z as usize >= rows - 1 {
+                return None;
+            }
+            x = next_x as usize;
+            z = next_z as usize;
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_bounding_box() {
+        let sphere = Sphere::new(Vector3::new(1.0, 2.0, 3.0), 0.5, Material::none());
+        let bounds = sphere.bounding_box().unwrap();
+        assert_eq!(bounds.min(), Vector3::new(0.5, 1.5, 2.5));
+        assert_eq!(bounds.max(), Vector3::new(1.5, 2.5, 3.5));
+    }
+
+    #[test]
+    fn plane_is_unbounded() {
+        let plane = Plane::new(nalgebra::zero(), Vector3::y(), Material::none());
+        assert!(plane.bounding_box().is_none());
+    }
+
+    #[test]
+    fn sphere_uv_at_positive_x() {
+        let sphere = Sphere::new(nalgebra::zero(), 1.0, Material::none());
+        let info = sphere.ray_intersect(Vector3::new(5.0, 0.0, 0.0), -Vector3::x()).unwrap();
+        assert!((info.uv.0 - 0.5).abs() < 1e-5 && (info.uv.1 - 0.5).abs() < 1e-5, "{:?}", info.uv);
+        // `u` grows towards +z
+        assert!((info.tangent - Vector3::z()).norm() < 1e-5, "{:?}", info.tangent);
+    }
+
+    #[test]
+    fn triangle_intersection() {
+        let triangle = Triangle::new(
+            Vector3::new(-1.0, -1.0, -3.0),
+            Vector3::new(1.0, -1.0, -3.0),
+            Vector3::new(0.0, 1.0, -3.0),
+            Material::none(),
+        );
+        let info = triangle.ray_intersect(nalgebra::zero(), -Vector3::z()).unwrap();
+        assert!((info.dist - 3.0).abs() < 1e-5, "{}", info.dist);
+        // counter-clockwise as seen from the origin, so facing it
+        assert!((info.normal - Vector3::z()).norm() < 1e-5, "{:?}", info.normal);
+
+        // parallel to the plane, and pointing away from it
+        assert!(triangle.ray_intersect(nalgebra::zero(), Vector3::x()).is_none());
+        assert!(triangle.ray_intersect(nalgebra::zero(), Vector3::z()).is_none());
+    }
+
+    #[test]
+    fn torus_hole_misses() {
+        let torus = Torus::new(nalgebra::zero(), Vector3::y(), 2.0, 0.5, Material::none());
+        assert!(torus.ray_intersect(Vector3::new(0.0, 5.0, 0.0), -Vector3::y()).is_none());
+
+        // straight through the tube, entering at the outer edge
+        let info = torus.ray_intersect(Vector3::new(5.0, 0.0, 0.0), -Vector3::x()).unwrap();
+        assert!((info.dist - 2.5).abs() < 1e-3, "{}", info.dist);
+        assert!((info.normal - Vector3::x()).norm() < 1e-3, "{:?}", info.normal);
+    }
+
+    #[test]
+    fn difference_carves_hole() {
+        let carved = Difference::new(
+            Sphere::new(nalgebra::zero(), 1.0, Material::none()),
+            Sphere::new(Vector3::new(0.0, 0.0, 1.0), 0.5, Material::none()),
+        );
+        // the outer surface is gone where the small sphere was, the ray meets the bottom of
+        // the hole instead, facing back out of it
+        let info = carved.ray_intersect(Vector3::new(0.0, 0.0, 5.0), -Vector3::z()).unwrap();
+        assert!((info.dist - 4.5).abs() < 1e-4, "{}", info.dist);
+        assert!((info.normal - Vector3::z()).norm() < 1e-4, "{:?}", info.normal);
+
+        // away from the hole the outer surface is untouched
+        let info = carved.ray_intersect(Vector3::new(5.0, 0.0, 0.0), -Vector3::x()).unwrap();
+        assert!((info.dist - 4.0).abs() < 1e-4, "{}", info.dist);
+        assert!((info.normal - Vector3::x()).norm() < 1e-4, "{:?}", info.normal);
+    }
+
+    #[test]
+    fn equator_and_cell_uv() {
+        let sphere = Sphere::new(nalgebra::zero(), 1.0, Material::none());
+        let info = sphere.ray_intersect(Vector3::new(0.0, 0.0, 5.0), -Vector3::z()).unwrap();
+        assert!((info.uv.1 - 0.5).abs() < 1e-5, "{:?}", info.uv);
+
+        let board = Checkerboard::infinite(
+            nalgebra::zero(),
+            (Vector3::x() * 2.0, Vector3::z() * 2.0),
+            (Material::none(), Material::none()),
+        );
+        let info = board.ray_intersect(Vector3::new(3.0, 1.0, -1.0), -Vector3::y()).unwrap();
+        assert!((info.uv.0 - 0.5).abs() < 1e-5 && (info.uv.1 - 0.5).abs() < 1e-5, "{:?}", info.uv);
+    }
+
+    #[test]
+    fn round_ellipsoid_matches_sphere() {
+        let center = Vector3::new(1.0, -2.0, -6.0);
+        let sphere = Sphere::new(center, 1.5, Material::none());
+        let ellipsoid = Ellipsoid::new(center, Vector3::repeat(1.5), Material::none());
+        let mut sampler = Sampler::new(5);
+        for _ in 0..100 {
+            // aimed around the center, hitting and missing, and some from inside
+            let orig = if sampler.next_f32() < 0.2 { center } else { nalgebra::zero() };
+            let target = center + Vector3::new(
+                sampler.next_f32() * 4.0 - 2.0,
+                sampler.next_f32() * 4.0 - 2.0,
+                sampler.next_f32() * 4.0 - 2.0,
+            );
+            let dir = (target - orig).normalize();
+            match (sphere.ray_intersect(orig, dir), ellipsoid.ray_intersect(orig, dir)) {
+                (Some(a), Some(b)) => {
+                    assert!((a.dist - b.dist).abs() < 1e-4, "{} vs {}", a.dist, b.dist);
+                    assert!((a.normal - b.normal).norm() < 1e-4, "{:?} vs {:?}", a.normal, b.normal);
+                },
+                (a, b) => assert_eq!(a.is_some(), b.is_some(), "{:?}", dir),
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_plane() {
+        // the floor turned a quarter around z into a wall facing -x, then moved out to x = 3
+        let plane = Transformed::new(
+            Plane::new(nalgebra::zero(), Vector3::y(), Material::none()),
+            Similarity3::new(
+                Vector3::new(3.0, 0.0, 0.0),
+                Vector3::z() * std::f32::consts::FRAC_PI_2,
+                1.0,
+            ),
+        );
+        let info = plane.ray_intersect(Vector3::new(0.0, 1.0, 0.0), Vector3::x()).unwrap();
+        assert!((info.dist - 3.0).abs() < 1e-5, "{}", info.dist);
+        assert!((info.hit - Vector3::new(3.0, 1.0, 0.0)).norm() < 1e-5, "{:?}", info.hit);
+        assert!((info.normal + Vector3::x()).norm() < 1e-5, "{:?}", info.normal);
+
+        // running down the wall instead of onto the floor
+        assert!(plane.ray_intersect(Vector3::new(0.0, 1.0, 0.0), -Vector3::y()).is_none());
+    }
+
+    #[test]
+    fn quad_mesh_distance() {
+        let mesh = TriangleMesh::new(
+            vec![
+                Vector3::new(-1.0, -1.0, -4.0),
+                Vector3::new(1.0, -1.0, -4.0),
+                Vector3::new(1.0, 1.0, -4.0),
+                Vector3::new(-1.0, 1.0, -4.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+            Material::none(),
+        );
+        assert_eq!(mesh.face_normals(), &[Vector3::z(), Vector3::z()]);
+
+        // one ray into each triangle, either side of the shared diagonal
+        for &target in &[Vector3::new(0.5, -0.5, -4.0), Vector3::new(-0.5, 0.5, -4.0)] {
+            let info = mesh.ray_intersect(nalgebra::zero(), target.normalize()).unwrap();
+            assert!((info.dist - target.norm()).abs() < 1e-5, "{}", info.dist);
+            assert!((info.hit - target).norm() < 1e-5, "{:?}", info.hit);
+        }
+        let beside = Vector3::new(2.0, 0.0, -4.0).normalize();
+        assert!(mesh.ray_intersect(nalgebra::zero(), beside).is_none());
+    }
+
+    #[test]
+    fn icosphere_shades_smoothly() {
+        let t = (1.0 + f32::sqrt(5.0)) / 2.0;
+        let vertices: Vec<_> = [
+            [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+            [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+            [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+        ]
+        .iter()
+        .map(|&v| Vector3::from(v).normalize())
+        .collect();
+        let faces = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        let flat = TriangleMesh::new(vertices.clone(), faces.clone(), Material::none());
+        // on a unit sphere the normal at each vertex is the vertex itself
+        let smooth = TriangleMesh::new(vertices.clone(), faces, Material::none())
+            .with_vertex_normals(vertices);
+
+        // how far the shading normal strays from that of the sphere being approximated
+        let worst_error = |mesh: &TriangleMesh| {
+            let mut sampler = Sampler::new(9);
+            (0..500)
+                .map(|_| {
+                    let target = Vector3::new(
+                        sampler.next_f32() - 0.5,
+                        sampler.next_f32() - 0.5,
+                        sampler.next_f32() - 0.5,
+                    );
+                    let orig = Vector3::new(0.0, 0.0, 5.0);
+                    let info = mesh.ray_intersect(orig, (target - orig).normalize()).unwrap();
+                    (info.normal - info.hit.normalize()).norm()
+                })
+                .fold(0.0, f32::max)
+        };
+        let (flat, smooth) = (worst_error(&flat), worst_error(&smooth));
+        assert!(smooth < 0.05 && flat > 0.3, "smooth {} flat {}", smooth, flat);
+    }
+
+    #[test]
+    fn double_sided_board_from_below() {
+        let board = |double_sided: bool| {
+            let board = Checkerboard::new(
+                Vector3::new(-2.0, 0.0, -2.0),
+                (Vector3::z(), Vector3::x()),
+                (4, 4),
+                (Material::none(), Material::none()),
+            );
+            if double_sided { board.with_double_sided() } else { board }
+        };
+        let orig = Vector3::new(0.5, -3.0, 0.5);
+        let info = board(true).ray_intersect(orig, Vector3::y()).unwrap();
+        assert!((info.dist - 3.0).abs() < 1e-5, "{}", info.dist);
+        assert!((info.normal + Vector3::y()).norm() < 1e-5, "{:?}", info.normal);
+
+        // one-sided boards keep facing up, away from the ray
+        let info = board(false).ray_intersect(orig, Vector3::y()).unwrap();
+        assert!((info.normal - Vector3::y()).norm() < 1e-5, "{:?}", info.normal);
+    }
+
+    #[test]
+    fn infinite_board_alternates_across_origin() {
+        let white = Material::color([1.0; 3], 1.0);
+        let black = Material::color([0.0; 3], 1.0);
+        let cell_dir = (Vector3::x(), Vector3::z());
+        let board = Checkerboard::infinite(nalgebra::zero(), cell_dir, (white, black));
+        let color_at = |x: f32, z: f32| {
+            let info = board.ray_intersect(Vector3::new(x, 1.0, z), -Vector3::y()).unwrap();
+            info.material.diffuse_color().unwrap()[0]
+        };
+        // mirrored through the origin, cells keep their color; mirrored along one axis, they flip
+        for &(x, z) in &[(0.5, 0.5), (1.5, 0.5), (0.5, 2.5), (3.5, 1.5)] {
+            assert_eq!(color_at(x, z), color_at(-x, -z), "({}, {})", x, z);
+            assert_ne!(color_at(x, z), color_at(-x, z), "({}, {})", x, z);
+            assert_ne!(color_at(x, z), color_at(x, -z), "({}, {})", x, z);
+        }
+    }
+
+    #[test]
+    fn flat_height_field_is_a_plane() {
+        let field = HeightField::new(
+            Vector3::new(-2.0, 0.0, -2.0),
+            0.5,
+            (9, 9),
+            vec![0.5; 81],
+            Material::none(),
+        );
+        let plane = Plane::new(Vector3::new(0.0, 0.5, 0.0), Vector3::y(), Material::none());
+        let orig = Vector3::new(0.0, 3.0, 5.0);
+        let mut sampler = Sampler::new(11);
+        for _ in 0..100 {
+            let (x, z) = (sampler.next_f32() * 3.8 - 1.9, sampler.next_f32() * 3.8 - 1.9);
+            let dir = (Vector3::new(x, 0.5, z) - orig).normalize();
+            let terrain = field.ray_intersect(orig, dir).unwrap();
+            let flat = plane.ray_intersect(orig, dir).unwrap();
+            assert!((terrain.dist - flat.dist).abs() < 1e-4, "{} vs {}", terrain.dist, flat.dist);
+            assert!((terrain.normal - flat.normal).norm() < 1e-5, "{:?}", terrain.normal);
+        }
+        // past the edge of the terrain, where the plane carries on
+        let dir = (Vector3::new(3.0, 0.5, 0.0) - orig).normalize();
+        assert!(field.ray_intersect(orig, dir).is_none());
+    }
 }
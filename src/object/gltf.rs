@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use nalgebra::{Matrix4, Vector3, Vector4, U3};
+use crate::{
+    material::Material,
+    object::TriangleMesh,
+    RenderError,
+};
+
+/// Loads every triangle primitive in the default scene of a `.gltf` or `.glb` file, placed by
+/// its node transforms, as one mesh each.
+///
+/// Metallic-roughness factors become a PBR material; textures, animations, cameras and skins
+/// are ignored.
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<Vec<TriangleMesh>, RenderError> {
+    let (document, buffers, _) = ::gltf::import(path).map_err(gltf_error)?;
+    meshes(&document, &buffers)
+}
+
+/// Like `load_gltf`, for a `.glb` file or a `.gltf` file with embedded buffers, already in
+/// memory.
+pub fn parse_gltf(bytes: &[u8]) -> Result<Vec<TriangleMesh>, RenderError> {
+    let (document, buffers, _) = ::gltf::import_slice(bytes).map_err(gltf_error)?;
+    meshes(&document, &buffers)
+}
+
+fn gltf_error(err: ::gltf::Error) -> RenderError {
+    RenderError::Parse(err.to_string())
+}
+
+fn meshes(
+    document: &::gltf::Document,
+    buffers: &[::gltf::buffer::Data],
+) -> Result<Vec<TriangleMesh>, RenderError> {
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| RenderError::Parse("glTF file has no scene".to_string()))?;
+
+    let mut meshes = Vec::new();
+    for node in scene.nodes() {
+        collect_node(&node, Matrix4::identity(), buffers, &mut meshes)?;
+    }
+    Ok(meshes)
+}
+
+fn collect_node(
+    node: &::gltf::Node,
+    parent: Matrix4<f32>,
+    buffers: &[::gltf::buffer::Data],
+    meshes: &mut Vec<TriangleMesh>,
+) -> Result<(), RenderError> {
+    let local = node.transform().matrix();
+    let transform = parent * Matrix4::from_fn(|r, c| local[c][r]);
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                continue;
+            }
+            meshes.push(load_primitive(&primitive, &transform, buffers)?);
+        }
+    }
+    for child in node.children() {
+        collect_node(&child, transform, buffers, meshes)?;
+    }
+    Ok(())
+}
+
+fn load_primitive(
+    primitive: &::gltf::Primitive,
+    transform: &Matrix4<f32>,
+    buffers: &[::gltf::buffer::Data],
+) -> Result<TriangleMesh, RenderError> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+
+    let vertices: Vec<_> = reader
+        .read_positions()
+        .ok_or_else(|| RenderError::Parse("primitive has no positions".to_string()))?
+        .map(|[x, y, z]| {
+            let world = transform * Vector4::new(x, y, z, 1.0);
+            Vector3::new(world.x, world.y, world.z)
+        })
+        .collect();
+    let faces: Vec<[usize; 3]> = match reader.read_indices() {
+        Some(indices) => {
+            let indices: Vec<_> = indices.into_u32().map(|i| i as usize).collect();
+            indices.chunks(3).filter(|face| face.len() == 3).map(|f| [f[0], f[1], f[2]]).collect()
+        },
+        None => (0..vertices.len() / 3).map(|i| [i * 3, i * 3 + 1, i * 3 + 2]).collect(),
+    };
+    if faces.iter().flatten().any(|&i| i >= vertices.len()) {
+        return Err(RenderError::Parse("primitive index out of range".to_string()));
+    }
+
+    let pbr = primitive.material().pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let material = Material::pbr([r, g, b], pbr.metallic_factor(), pbr.roughness_factor());
+
+    let mut mesh = TriangleMesh::new(vertices, faces, material);
+    if let Some(normals) = reader.read_normals() {
+        // normals transform by the inverse transpose of the linear part
+        let normal_matrix = transform
+            .fixed_slice::<U3, U3>(0, 0)
+            .into_owned()
+            .try_inverse()
+            .ok_or_else(|| RenderError::Parse("node transform is singular".to_string()))?
+            .transpose();
+        let normals = normals.map(|n| normal_matrix * Vector3::from(n)).collect();
+        mesh = mesh.with_vertex_normals(normals);
+    }
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        // glTF puts the origin of texture space at the top left
+        let uvs = uvs.into_f32().map(|[u, v]| (u, 1.0 - v)).collect();
+        mesh = mesh.with_vertex_uvs(uvs);
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_triangle_glb() {
+        // one triangle in the xy plane, positions only
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0]}],
+            "nodes": [{"mesh": 0}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]
+            }],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{"byteLength": 36}]
+        }"#;
+        let mut json = json.as_bytes().to_vec();
+        // chunks are padded to 4 bytes, JSON with spaces
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut bin = Vec::with_capacity(36);
+        for &v in &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend(&v.to_le_bytes());
+        }
+
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(total);
+        glb.extend(b"glTF");
+        glb.extend(&2u32.to_le_bytes());
+        glb.extend(&(total as u32).to_le_bytes());
+        glb.extend(&(json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(&json);
+        glb.extend(&(bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(&bin);
+
+        let meshes = parse_gltf(&glb).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].vertices().len(), 3);
+        assert_eq!(meshes[0].faces(), &[[0, 1, 2]]);
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use nalgebra::Vector3;
+use crate::{
+    material::Material,
+    object::{Triangle, TriangleMesh},
+    RenderError,
+};
+
+pub fn load_obj<P: AsRef<Path>>(path: P, material: Material) -> Result<Vec<Triangle>, RenderError> {
+    let file = std::fs::File::open(path).map_err(RenderError::Io)?;
+    parse_obj(std::io::BufReader::new(file), material)
+}
+
+pub fn parse_obj<R: BufRead>(reader: R, material: Material) -> Result<Vec<Triangle>, RenderError> {
+    let obj = read_obj(reader)?;
+    let triangles = obj
+        .faces
+        .iter()
+        .map(|&[i0, i1, i2]| {
+            Triangle::new(obj.vertices[i0], obj.vertices[i1], obj.vertices[i2], material.clone())
+        })
+        .collect();
+    Ok(triangles)
+}
+
+/// Loads an OBJ file as a single mesh, shading faces after a `usemtl` statement with the
+/// material of that name in `materials`. Faces before any `usemtl`, or naming a material that
+/// isn't in `materials`, use `default`.
+pub fn load_obj_mesh<P: AsRef<Path>>(
+    path: P,
+    materials: &HashMap<String, Material>,
+    default: Material,
+) -> Result<TriangleMesh, RenderError> {
+    let file = std::fs::File::open(path).map_err(RenderError::Io)?;
+    parse_obj_mesh(std::io::BufReader::new(file), materials, default)
+}
+
+pub fn parse_obj_mesh<R: BufRead>(
+    reader: R,
+    materials: &HashMap<String, Material>,
+    default: Material,
+) -> Result<TriangleMesh, RenderError> {
+    Ok(build_mesh(read_obj(reader)?, materials, default))
+}
+
+/// Like `load_obj_mesh`, with the materials read from the `.mtl` files its `mtllib` statements
+/// name, relative to the OBJ file.
+pub fn load_obj_with_mtl<P: AsRef<Path>>(path: P, default: Material) -> Result<TriangleMesh, RenderError> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).map_err(RenderError::Io)?;
+    let obj = read_obj(std::io::BufReader::new(file))?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = HashMap::new();
+    for library in &obj.libraries {
+        materials.extend(load_mtl(dir.join(library))?);
+    }
+    Ok(build_mesh(obj, &materials, default))
+}
+
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Material>, RenderError> {
+    let file = std::fs::File::open(path).map_err(RenderError::Io)?;
+    parse_mtl(std::io::BufReader::new(file))
+}
+
+/// Reads the materials of a Wavefront `.mtl` file, keyed by name.
+///
+/// `Kd` becomes the diffuse color, `Ks` and `Ns` the specular highlight, and `Ni` with `d`
+/// refraction for materials that aren't fully opaque. Texture maps and other statements are
+/// ignored.
+pub fn parse_mtl<R: BufRead>(reader: R) -> Result<HashMap<String, Material>, RenderError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(RenderError::Io)?;
+        let line_no = line_idx + 1;
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Some(statement) => statement,
+            None => continue,
+        };
+        if statement == "newmtl" {
+            let name = tokens.next().ok_or_else(|| parse_error(line_no, "missing material name"))?;
+            if let Some((name, entry)) = current.take() {
+                materials.insert(name, entry.into_material());
+            }
+            current = Some((name.to_string(), MtlEntry::default()));
+            continue;
+        }
+
+        let entry = match &mut current {
+            Some((_, entry)) => entry,
+            // comments and anything before the first material
+            None => continue,
+        };
+        let mut number = || {
+            tokens
+                .next()
+                .and_then(|token| token.parse::<f32>().ok())
+                .ok_or_else(|| parse_error(line_no, "invalid number"))
+        };
+        match statement {
+            "Kd" => entry.diffuse = Some([number()?, number()?, number()?]),
+            "Ks" => entry.specular = Some([number()?, number()?, number()?]),
+            "Ns" => entry.shininess = Some(number()?),
+            "Ni" => entry.index = Some(number()?),
+            "d" => entry.dissolve = Some(number()?),
+            _ => {},
+        }
+    }
+    if let Some((name, entry)) = current {
+        materials.insert(name, entry.into_material());
+    }
+
+    Ok(materials)
+}
+
+/// Statements of one `newmtl` block that map onto a `Material`.
+#[derive(Debug, Default)]
+struct MtlEntry {
+    diffuse: Option<[f32; 3]>,
+    specular: Option<[f32; 3]>,
+    shininess: Option<f32>,
+    index: Option<f32>,
+    dissolve: Option<f32>,
+}
+
+impl MtlEntry {
+    fn into_material(self) -> Material {
+        let mut material = match self.diffuse {
+            Some(color) => Material::color(color, 1.0),
+            None => Material::none(),
+        };
+        if let Some([r, g, b]) = self.specular {
+            let albedo = (r + g + b) / 3.0;
+            if albedo > 0.0 {
+                material = material.with_specular(self.shininess.unwrap_or(1.0), albedo);
+            }
+        }
+        match self.dissolve {
+            Some(dissolve) if dissolve < 1.0 => {
+                material = material.with_refract(self.index.unwrap_or(1.0), 1.0 - dissolve);
+            },
+            _ => {},
+        }
+        material
+    }
+}
+
+fn build_mesh(obj: ObjData, materials: &HashMap<String, Material>, default: Material) -> TriangleMesh {
+    if obj.face_groups.iter().all(Option::is_none) {
+        return TriangleMesh::new(obj.vertices, obj.faces, default);
+    }
+
+    // slot 0 holds the default, the others are filled in order of first use
+    let mut mesh_materials = vec![default];
+    let mut slots: HashMap<&str, usize> = HashMap::new();
+    let face_materials = obj
+        .face_groups
+        .iter()
+        .map(|name| {
+            let material = name.as_ref().and_then(|name| materials.get_key_value(name));
+            match material {
+                Some((name, material)) => *slots.entry(name.as_str()).or_insert_with(|| {
+                    mesh_materials.push(material.clone());
+                    mesh_materials.len() - 1
+                }),
+                None => 0,
+            }
+        })
+        .collect();
+    TriangleMesh::new(obj.vertices, obj.faces, Material::none())
+        .with_face_materials(mesh_materials, face_materials)
+}
+
+/// Geometry of an OBJ file, triangulated, with the `usemtl` name in effect for each face and
+/// the material libraries it refers to.
+struct ObjData {
+    vertices: Vec<Vector3<f32>>,
+    faces: Vec<[usize; 3]>,
+    face_groups: Vec<Option<String>>,
+    libraries: Vec<String>,
+}
+
+fn read_obj<R: BufRead>(reader: R) -> Result<ObjData, RenderError> {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    let mut face_groups = Vec::new();
+    let mut current_material = None;
+    let mut libraries = Vec::new();
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(RenderError::Io)?;
+        let line_no = line_idx + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let mut coords = [0.0f32; 3];
+                for coord in coords.iter_mut() {
+                    *coord = tokens
+                        .next()
+                        .and_then(|token| token.parse().ok())
+                        .ok_or_else(|| parse_error(line_no, "invalid vertex"))?;
+                }
+                vertices.push(Vector3::from(coords));
+            },
+            Some("f") => {
+                let face = tokens
+                    .map(|token| resolve_index(token, vertices.len()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| parse_error(line_no, "invalid face index"))?;
+                if face.len() < 3 {
+                    return Err(parse_error(line_no, "face needs at least three vertices"));
+                }
+                // fan triangulation, fine for the convex polygons exporters produce
+                for pair in face[1..].windows(2) {
+                    faces.push([face[0], pair[0], pair[1]]);
+                    face_groups.push(current_material.clone());
+                }
+            },
+            Some("usemtl") => {
+                current_material = tokens.next().map(str::to_string);
+            },
+            Some("mtllib") => libraries.extend(tokens.map(str::to_string)),
+            // comments, normals, texture coordinates, groups...
+            _ => {},
+        }
+    }
+
+    Ok(ObjData {
+        vertices,
+        faces,
+        face_groups,
+        libraries,
+    })
+}
+
+fn parse_error(line_no: usize, message: &str) -> RenderError {
+    RenderError::Parse(format!("line {}: {}", line_no, message))
+}
+
+/// Resolves a face vertex reference like `3`, `3/1` or `-1//2` into an index into `vertices`.
+fn resolve_index(token: &str, vertex_count: usize) -> Option<usize> {
+    let index: isize = token.split('/').next()?.parse().ok()?;
+    let resolved = if index < 0 {
+        vertex_count as isize + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved as usize >= vertex_count {
+        None
+    } else {
+        Some(resolved as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_small_mtl() {
+        let mtl = "\
+# two materials
+newmtl red
+Kd 0.8 0.1 0.1
+Ks 0.5 0.5 0.5
+Ns 50
+map_Kd red.png
+
+newmtl glass
+Kd 0.1 0.1 0.1
+Ni 1.5
+d 0.2
+";
+        let materials = parse_mtl(mtl.as_bytes()).unwrap();
+        assert_eq!(materials.len(), 2);
+
+        let red = &materials["red"];
+        assert_eq!(red.diffuse_color(), Some([0.8, 0.1, 0.1]));
+        assert_eq!(red.diffuse_albedo(), Some(1.0));
+        assert_eq!(red.specular(), Some((50.0, 0.5)));
+        assert_eq!(red.refract(), None);
+
+        let glass = &materials["glass"];
+        assert_eq!(glass.specular(), None);
+        let (index, albedo) = glass.refract().unwrap();
+        assert_eq!(index, 1.5);
+        assert!((albedo - 0.8).abs() < 1e-6, "{}", albedo);
+    }
+
+    #[test]
+    fn cube_of_quads() {
+        let obj = "\
+# unit cube, one quad per side
+o cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+s off
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2 3 7 6
+";
+        let triangles = parse_obj(obj.as_bytes(), Material::none()).unwrap();
+        assert_eq!(triangles.len(), 12);
+    }
+
+
+    #[test]
+    fn two_faces_two_materials() {
+        let dir = std::env::temp_dir().join(format!("tiny-raytracer-mtl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mtl = "\
+newmtl red
+Kd 1 0 0
+
+newmtl green
+Kd 0 1 0
+";
+        // two triangles side by side, facing +z
+        let obj = "\
+mtllib two_faces.mtl
+v -1 -1 -3
+v 0 -1 -3
+v -1 1 -3
+v 1 -1 -3
+v 1 1 -3
+usemtl red
+f 1 2 3
+usemtl green
+f 2 4 5
+";
+        std::fs::write(dir.join("two_faces.mtl"), mtl).unwrap();
+        std::fs::write(dir.join("two_faces.obj"), obj).unwrap();
+        let mesh = load_obj_with_mtl(dir.join("two_faces.obj"), Material::none());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // lit by ambient light alone, so each face shows its own color
+        let mut scene = crate::Scene::new();
+        scene.set_ambient([1.0; 3]);
+        scene.push_object(mesh.unwrap());
+        let shade = |x: f32| scene.cast_ray(Vector3::new(x, -0.5, 0.0), -Vector3::z(), 1);
+        assert_eq!(shade(-0.7), [1.0, 0.0, 0.0]);
+        assert_eq!(shade(0.7), [0.0, 1.0, 0.0]);
+    }
+}
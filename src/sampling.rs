@@ -0,0 +1,91 @@
+use nalgebra::Vector3;
+use crate::math::onb_from_normal;
+
+/// Small deterministic pseudo-random generator (xorshift64*), so that stochastic effects render
+/// the same every time.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    state: u64,
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads nearby seeds apart, and never leaves the state at zero
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Self {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    /// Generator for the pixel at `(x, y)` in the given animation `frame`, independent of every
+    /// other pixel and frame.
+    pub fn for_pixel(x: u32, y: u32, frame: u32) -> Self {
+        Self::new(u64::from(x) | u64::from(y) << 21 | u64::from(frame) << 42)
+    }
+
+    /// Uniform sample in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        // top 24 bits fill the mantissa exactly
+        (bits >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform point in the unit disk.
+    pub fn unit_disk(&mut self) -> (f32, f32) {
+        let r = f32::sqrt(self.next_f32());
+        let phi = 2.0 * std::f32::consts::PI * self.next_f32();
+        (r * f32::cos(phi), r * f32::sin(phi))
+    }
+
+    /// Direction in the hemisphere around the unit vector `normal`, more likely near it in
+    /// proportion to the cosine of the angle between them.
+    pub fn cosine_hemisphere(&mut self, normal: Vector3<f32>) -> Vector3<f32> {
+        // uniform on the unit disk, projected up onto the hemisphere
+        let (x, y) = self.unit_disk();
+        let (e0, e1) = onb_from_normal(normal);
+        e0 * x + e1 * y + normal * f32::sqrt(f32::max(0.0, 1.0 - x * x - y * y))
+    }
+
+    /// Uniform direction on the unit sphere.
+    pub fn unit_sphere(&mut self) -> Vector3<f32> {
+        let z = 1.0 - 2.0 * self.next_f32();
+        let r = f32::sqrt(f32::max(0.0, 1.0 - z * z));
+        let phi = 2.0 * std::f32::consts::PI * self.next_f32();
+        Vector3::new(r * f32::cos(phi), r * f32::sin(phi), z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(mut sampler: Sampler) -> Vec<f32> {
+        (0..16).map(|_| sampler.next_f32()).collect()
+    }
+
+    #[test]
+    fn pixels_are_reproducible_and_independent() {
+        assert_eq!(sequence(Sampler::for_pixel(3, 4, 0)), sequence(Sampler::for_pixel(3, 4, 0)));
+        assert_ne!(sequence(Sampler::for_pixel(3, 4, 0)), sequence(Sampler::for_pixel(4, 3, 0)));
+        assert_ne!(sequence(Sampler::for_pixel(3, 4, 0)), sequence(Sampler::for_pixel(3, 4, 1)));
+        assert!(sequence(Sampler::new(0)).iter().all(|&x| (0.0..1.0).contains(&x)));
+    }
+
+    #[test]
+    fn shapes_stay_in_bounds() {
+        let mut sampler = Sampler::new(5);
+        let normal = Vector3::new(1.0, 2.0, -2.0).normalize();
+        for _ in 0..100 {
+            let (x, y) = sampler.unit_disk();
+            assert!(x * x + y * y <= 1.0);
+            let dir = sampler.cosine_hemisphere(normal);
+            assert!((dir.norm() - 1.0).abs() < 1e-4 && dir.dot(&normal) >= 0.0, "{:?}", dir);
+            assert!((sampler.unit_sphere().norm() - 1.0).abs() < 1e-4);
+        }
+    }
+}
@@ -1,32 +1,456 @@
+mod builder;
+#[cfg(feature = "serde")]
+mod json;
+
+use std::sync::OnceLock;
+
 use nalgebra::Vector3;
 use crate::{
-    framebuffer::Framebuffer,
-    material::{Diffuse, DiffuseKind, Refract, Specular},
-    math::{reflect, refract},
-    object::{IntersectionInfo, Object},
+    accel::Bvh,
+    camera::Camera,
+    framebuffer::{luminance, Framebuffer},
+    material::{Clearcoat, Refract, Specular, Texture, TextureFilter},
+    math::{
+        offset_origin,
+        onb_from_normal,
+        reflect,
+        refract,
+        refract_fresnel,
+        schlick,
+        smoothstep,
+    },
+    object::{Aabb, FogVolume, IntersectionInfo, Object},
+    sampling::Sampler,
+    RenderError,
 };
 
+pub use builder::SceneBuilder;
+#[cfg(feature = "serde")]
+pub use json::from_json;
+
 const AIR_REFRACTION_INDEX: f32 = 1.0;
+/// Rays spread over a glossy reflection lobe at the first hit of a path.
+const GLOSSY_SAMPLES: u32 = 8;
+const CLEARCOAT_REFRACTION_INDEX: f32 = 1.5;
+/// Bounces a path takes before Russian roulette may end it.
+const ROULETTE_MIN_BOUNCES: u32 = 3;
+
+/// Shading normal from a normal map `sample`, in the tangent frame of the surface.
+fn perturb_normal(normal: Vector3<f32>, tangent: Vector3<f32>, sample: [f32; 3]) -> Vector3<f32> {
+    // re-orthogonalize, as interpolated normals drift away from the tangent
+    let tangent = (tangent - normal * normal.dot(&tangent)).normalize();
+    let bitangent = normal.cross(&tangent);
+    let local = Vector3::from(sample) * 2.0 - Vector3::repeat(1.0);
+    (tangent * local.x + bitangent * local.y + normal * local.z).normalize()
+}
+
+/// How the intensity of a light decreases with distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FalloffKind {
+    None,
+    Linear,
+    Quadratic,
+}
+
+#[derive(Debug, Clone)]
+enum LightKind {
+    Point {
+        position: Vector3<f32>,
+        falloff: FalloffKind,
+    },
+    Directional {
+        direction: Vector3<f32>,
+    },
+    Spot {
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        cos_inner: f32,
+        cos_outer: f32,
+    },
+    Area {
+        corner: Vector3<f32>,
+        edges: (Vector3<f32>, Vector3<f32>),
+        samples: u32,
+    },
+}
+
+/// Random direction within a cone around the unit vector `dir`, with a half-angle of
+/// `roughness` times a right angle.
+fn jitter_dir(dir: Vector3<f32>, roughness: f32, sampler: &mut Sampler) -> Vector3<f32> {
+    let max_angle = f32::min(roughness, 1.0) * std::f32::consts::FRAC_PI_2;
+    // uniform over the spherical cap
+    let cos_theta = 1.0 - sampler.next_f32() * (1.0 - f32::cos(max_angle));
+    let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
+    let phi = 2.0 * std::f32::consts::PI * sampler.next_f32();
+    let (e0, e1) = onb_from_normal(dir);
+    e0 * (sin_theta * f32::cos(phi)) + e1 * (sin_theta * f32::sin(phi)) + dir * cos_theta
+}
+
+/// Progress of a ray along its path from the camera.
+#[derive(Debug, Clone, Copy)]
+struct Path {
+    /// Rays left to trace, counting this one.
+    remaining: u32,
+    bounces: u32,
+    /// Fraction of the light arriving along this ray that makes it back to the camera.
+    throughput: f32,
+    /// When within the shutter interval the path is traced, for motion blur.
+    time: f32,
+    /// Color channel the path carries alone since it was split up by dispersion.
+    channel: Option<usize>,
+}
+
+impl Path {
+    fn new(max_depth: u32, time: f32) -> Self {
+        Self {
+            remaining: max_depth,
+            bounces: 0,
+            throughput: 1.0,
+            time,
+            channel: None,
+        }
+    }
+
+    /// The path continued by a secondary ray contributing `weight` of the color here.
+    fn bounce(self, weight: f32) -> Self {
+        Self {
+            remaining: self.remaining - 1,
+            bounces: self.bounces + 1,
+            throughput: self.throughput * weight,
+            ..self
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Light {
-    position: Vector3<f32>,
-    intensity: f32,
+    kind: LightKind,
+    intensity: Vector3<f32>,
+    casts_shadows: bool,
+    shadow_color: [f32; 3],
 }
 
 impl Light {
     pub fn new(position: Vector3<f32>, intensity: f32) -> Self {
+        Self::with_falloff(position, intensity, FalloffKind::None)
+    }
+
+    /// A point light with a per-channel intensity of `color`.
+    pub fn colored(position: Vector3<f32>, color: [f32; 3]) -> Self {
+        Self::new(position, 1.0).with_color(color)
+    }
+
+    /// Replaces the intensity of the light with a per-channel `color`.
+    pub fn with_color(self, color: [f32; 3]) -> Self {
+        Self {
+            intensity: Vector3::from(color),
+            ..self
+        }
+    }
+
+    /// Whether objects in the way block the light. Turning shadows off suits fill lights. On by
+    /// default.
+    pub fn with_shadows(self, casts_shadows: bool) -> Self {
+        Self {
+            casts_shadows,
+            ..self
+        }
+    }
+
+    /// Fraction of the light, per channel, that still reaches points in its shadow. Black, for
+    /// fully dark shadows, by default.
+    pub fn with_shadow_color(self, shadow_color: [f32; 3]) -> Self {
+        Self {
+            shadow_color,
+            ..self
+        }
+    }
+
+    /// A light whose `intensity` is divided by the distance (`Linear`) or its square
+    /// (`Quadratic`), so that it's exactly `intensity` one unit away.
+    pub fn with_falloff(position: Vector3<f32>, intensity: f32, falloff: FalloffKind) -> Self {
+        Self {
+            kind: LightKind::Point { position, falloff },
+            intensity: Vector3::repeat(intensity),
+            casts_shadows: true,
+            shadow_color: [0.0; 3],
+        }
+    }
+
+    /// A light infinitely far away, such as the sun, shining along `direction` everywhere.
+    pub fn directional(direction: Vector3<f32>, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional { direction: direction.normalize() },
+            intensity: Vector3::repeat(intensity),
+            casts_shadows: true,
+            shadow_color: [0.0; 3],
+        }
+    }
+
+    /// A light at `position` shining along `direction`, at full `intensity` within `inner`
+    /// radians of it and fading out smoothly up to `outer` radians.
+    pub fn spot(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        intensity: f32,
+        inner: f32,
+        outer: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                position,
+                direction: direction.normalize(),
+                cos_inner: f32::cos(inner),
+                cos_outer: f32::cos(outer),
+            },
+            intensity: Vector3::repeat(intensity),
+            casts_shadows: true,
+            shadow_color: [0.0; 3],
+        }
+    }
+
+    /// A rectangular emitter spanned by `edges` from `corner`, casting soft shadows. Visibility
+    /// is estimated from `samples` shadow rays per shading point, trading noise for speed.
+    pub fn area(
+        corner: Vector3<f32>,
+        edges: (Vector3<f32>, Vector3<f32>),
+        intensity: f32,
+        samples: u32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Area { corner, edges, samples: u32::max(samples, 1) },
+            intensity: Vector3::repeat(intensity),
+            casts_shadows: true,
+            shadow_color: [0.0; 3],
+        }
+    }
+
+    /// Where the light is, or the center of an area light. Directional lights have no position
+    /// and report the origin.
+    pub fn position(&self) -> Vector3<f32> {
+        match self.kind {
+            LightKind::Point { position, .. } | LightKind::Spot { position, .. } => position,
+            LightKind::Directional { .. } => nalgebra::zero(),
+            LightKind::Area { corner, edges, .. } => corner + (edges.0 + edges.1) / 2.0,
+        }
+    }
+
+    /// Moves the light to `position`, keeping its direction and shape. Directional lights are
+    /// everywhere at once and stay as they are.
+    pub fn set_position(&mut self, new_position: Vector3<f32>) {
+        match &mut self.kind {
+            LightKind::Point { position, .. } | LightKind::Spot { position, .. } => {
+                *position = new_position;
+            },
+            LightKind::Directional { .. } => {},
+            LightKind::Area { corner, edges, .. } => {
+                *corner = new_position - (edges.0 + edges.1) / 2.0;
+            },
+        }
+    }
+
+    /// Intensity of each color channel. Colored lights differ per channel, so this is a vector;
+    /// `max_intensity` gives a single number, as passed to `Light::new` for white lights.
+    pub fn intensity(&self) -> Vector3<f32> {
+        self.intensity
+    }
+
+    /// Intensity of the brightest channel.
+    pub fn max_intensity(&self) -> f32 {
+        self.intensity.max()
+    }
+
+    pub fn set_intensity(&mut self, intensity: Vector3<f32>) {
+        self.intensity = intensity;
+    }
+
+    /// Unit direction towards the light from `hit`, the distance to it and the intensity
+    /// arriving there.
+    fn illuminate(&self, hit: Vector3<f32>) -> (Vector3<f32>, f32, Vector3<f32>) {
+        match self.kind {
+            LightKind::Point { position, falloff } => {
+                let raw_light_dir = position - hit;
+                let dist = raw_light_dir.norm();
+                let intensity = match falloff {
+                    FalloffKind::None => self.intensity,
+                    FalloffKind::Linear => self.intensity / dist,
+                    FalloffKind::Quadratic => self.intensity / (dist * dist),
+                };
+                (raw_light_dir / dist, dist, intensity)
+            },
+            LightKind::Directional { direction } => (-direction, f32::INFINITY, self.intensity),
+            LightKind::Spot { position, direction, cos_inner, cos_outer } => {
+                let raw_light_dir = position - hit;
+                let dist = raw_light_dir.norm();
+                let light_dir = raw_light_dir / dist;
+                let cos_angle = -light_dir.dot(&direction);
+                let intensity = self.intensity * smoothstep(cos_outer, cos_inner, cos_angle);
+                (light_dir, dist, intensity)
+            },
+            LightKind::Area { corner, edges, .. } => {
+                // shade as if from the center, the samples only decide visibility
+                let raw_light_dir = corner + (edges.0 + edges.1) / 2.0 - hit;
+                let dist = raw_light_dir.norm();
+                (raw_light_dir / dist, dist, self.intensity)
+            },
+        }
+    }
+}
+
+/// What a ray sees when it misses every object.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid([f32; 3]),
+    /// Blends from `bottom` for rays pointing straight down to `top` for rays pointing straight
+    /// up.
+    Gradient {
+        top: [f32; 3],
+        bottom: [f32; 3],
+    },
+    /// An equirectangular panorama, with the +z direction at the center of the image and
+    /// straight up along its top edge.
+    Environment(Texture),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid([0.2, 0.7, 0.8])
+    }
+}
+
+impl Background {
+    /// An environment background from an equirectangular PNG, sampled bilinearly.
+    pub fn environment_from_png<R: std::io::Read>(r: R) -> Result<Self, RenderError> {
+        Ok(Background::Environment(Texture::from_png(r, TextureFilter::Bilinear)?))
+    }
+
+    fn color(&self, dir: Vector3<f32>) -> [f32; 3] {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let t = 0.5 * (dir.normalize().y + 1.0);
+                (Vector3::from(*bottom) * (1.0 - t) + Vector3::from(*top) * t).into()
+            },
+            Background::Environment(texture) => {
+                let dir = dir.normalize();
+                let u = 0.5 + f32::atan2(dir.x, dir.z) / (2.0 * std::f32::consts::PI);
+                let v = 0.5 + f32::asin(dir.y.clamp(-1.0, 1.0)) / std::f32::consts::PI;
+                texture.sample((u, v))
+            },
+        }
+    }
+}
+
+/// How `Scene::render` computes the light arriving along each camera ray.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Direct lighting from the scene's lights, plus mirror reflection and refraction.
+    Whitted,
+    /// Monte Carlo path tracing, averaging `samples` paths per camera ray. Only emissive
+    /// materials and the background give off light; the scene's lights are ignored.
+    PathTraced { samples: u32 },
+}
+
+/// Antialiasing that keeps sampling a pixel only while its samples disagree, spending rays on
+/// edges and noise rather than flat regions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel gets.
+    pub min_samples: u32,
+    /// Samples no pixel goes beyond.
+    pub max_samples: u32,
+    /// Standard error of the mean luminance below which a pixel counts as converged.
+    pub threshold: f32,
+}
+
+/// Parameters of a single `Scene::render` call.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    /// Vertical field of view in radians, for scenes without a camera of their own. Those are
+    /// seen from the origin looking down -z.
+    pub fov: f32,
+    /// Overrides the scene's `set_max_depth` when set.
+    pub max_depth: Option<u32>,
+    pub aa_samples: u32,
+    /// Overrides the scene's `set_background` when set.
+    pub background: Option<Background>,
+    /// Side of the square tiles the image is split into, each rendered as one unit of parallel
+    /// work. Doesn't affect the result.
+    pub tile_size: usize,
+    pub mode: RenderMode,
+    /// Times the shutter opens and closes, within `[0, 1]`, for motion blur. Each ray is traced
+    /// at a random time in between, showing moving objects smeared along their way. Everything
+    /// is seen at time 0 when unset.
+    pub shutter: Option<(f32, f32)>,
+    /// Replaces the `aa_samples` grid with a varying number of random samples per pixel when
+    /// set.
+    pub adaptive: Option<AdaptiveSampling>,
+    /// Camera rays to cast at most, counting every antialiasing, lens and path sample.
+    pub ray_budget: Option<u64>,
+    /// Wall-clock time to spend at most.
+    pub time_limit: Option<std::time::Duration>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
         Self {
-            position,
-            intensity,
+            width: 1024,
+            height: 768,
+            fov: std::f32::consts::PI / 3.0,
+            max_depth: None,
+            aa_samples: 1,
+            background: None,
+            tile_size: 32,
+            mode: RenderMode::Whitted,
+            shutter: None,
+            adaptive: None,
+            ray_budget: None,
+            time_limit: None,
         }
     }
 }
 
-#[derive(Default)]
 pub struct Scene {
     objects: Vec<Box<dyn Object + Sync>>,
+    bounds: Vec<Option<Aabb>>,
+    /// Built on first use after the objects change.
+    bvh: OnceLock<Bvh>,
+    volumes: Vec<FogVolume>,
     lights: Vec<Light>,
+    ambient: [f32; 3],
+    ambient_hemisphere: Option<([f32; 3], [f32; 3])>,
+    ao_samples: u32,
+    ao_radius: f32,
+    russian_roulette: bool,
+    background: Background,
+    hdr: bool,
+    max_depth: u32,
+    bias: f32,
+    camera: Option<Camera>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            bounds: Vec::new(),
+            bvh: OnceLock::new(),
+            volumes: Vec::new(),
+            lights: Vec::new(),
+            ambient: [0.0; 3],
+            ambient_hemisphere: None,
+            ao_samples: 0,
+            ao_radius: 1.0,
+            russian_roulette: false,
+            background: Background::default(),
+            hdr: false,
+            max_depth: 4,
+            bias: 1e-3,
+            camera: None,
+        }
+    }
 }
 
 impl Scene {
@@ -35,21 +459,187 @@ impl Scene {
     }
 
     pub fn push_object<T: Object + 'static>(&mut self, object: T) {
+        self.bounds.push(object.bounding_box());
         self.objects.push(Box::new(object));
+        self.bvh = OnceLock::new();
+    }
+
+    pub fn objects_len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Takes the object at `index` out of the scene, shifting the ones after it down by one.
+    /// This is O(n), and the acceleration structure is rebuilt on the next render.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_object(&mut self, index: usize) -> Box<dyn Object + Sync> {
+        self.bounds.remove(index);
+        let object = self.objects.remove(index);
+        self.bvh = OnceLock::new();
+        object
+    }
+
+    /// Box enclosing every finite object, leaving out unbounded ones such as planes. `None` if
+    /// there are no finite objects at all.
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds.iter().flatten().fold(None, |acc: Option<Aabb>, bounds| match acc {
+            Some(acc) => Some(acc.union(bounds)),
+            None => Some(bounds.clone()),
+        })
+    }
+
+    /// Fills a box with fog, seen by primary, reflected and refracted rays. Shadow rays and path
+    /// tracing go straight through it.
+    pub fn push_volume(&mut self, volume: FogVolume) {
+        self.volumes.push(volume);
     }
 
     pub fn push_light(&mut self, light: Light) {
         self.lights.push(light);
     }
 
-    fn test_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
-        let mut intersections: Vec<_> = self
-            .objects
+    pub fn iter_lights(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter()
+    }
+
+    /// Camera the scene was described with, for callers that don't bring their own.
+    pub fn camera(&self) -> Option<&Camera> {
+        self.camera.as_ref()
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = Some(camera);
+    }
+
+    /// Number of bounces traced for reflection and refraction in `render`, counting the primary
+    /// ray. 4 by default.
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    /// Distance secondary rays start off the surface they leave, to keep them from hitting it
+    /// again. Scenes with large coordinates need more to avoid shadow acne, at the cost of
+    /// shadows detaching from their casters. 1e-3 by default.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    /// Keeps colors brighter than 1 as they are, to be tone mapped on the whole framebuffer
+    /// later, instead of scaling each one down by its brightest channel.
+    pub fn set_hdr(&mut self, hdr: bool) {
+        self.hdr = hdr;
+    }
+
+    /// Light reaching every diffuse surface regardless of shadows, a cheap stand-in for
+    /// indirect lighting. Zero by default.
+    pub fn set_ambient(&mut self, ambient: [f32; 3]) {
+        self.ambient = ambient;
+    }
+
+    /// Adds ambient light that depends on which way a surface faces: `sky` for surfaces facing
+    /// straight up, `ground` for ones facing straight down, and a blend of the two in between.
+    pub fn set_ambient_hemisphere(&mut self, sky: [f32; 3], ground: [f32; 3]) {
+        self.ambient_hemisphere = Some((sky, ground));
+    }
+
+    /// Ambient light arriving at a surface facing `normal`.
+    fn ambient_light(&self, normal: Vector3<f32>) -> Vector3<f32> {
+        let ambient = Vector3::from(self.ambient);
+        match self.ambient_hemisphere {
+            Some((sky, ground)) => {
+                let t = (normal.y + 1.0) / 2.0;
+                ambient + Vector3::from(sky) * t + Vector3::from(ground) * (1.0 - t)
+            },
+            None => ambient,
+        }
+    }
+
+    /// Ends dim reflection and refraction paths early at random after a few bounces, scaling
+    /// up the ones that go on so that the average stays the same. Saves time on deep scenes at
+    /// the cost of noise. Off by default.
+    pub fn set_russian_roulette(&mut self, enabled: bool) {
+        self.russian_roulette = enabled;
+    }
+
+    /// Darkens the ambient light by the fraction of `samples` rays from each hit that run into
+    /// something within `radius`, shading creases and contact points. Off with 0 samples, the
+    /// default.
+    pub fn set_ambient_occlusion(&mut self, samples: u32, radius: f32) {
+        self.ao_samples = samples;
+        self.ao_radius = radius;
+    }
+
+    fn test_intersect(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        time: f32,
+    ) -> Option<IntersectionInfo> {
+        // objects expect a unit direction, so normalize it once for all of them
+        let dir = dir.normalize();
+        let bvh = self.bvh.get_or_init(|| Bvh::new(&self.bounds));
+        bvh.nearest(orig, dir, |idx| self.objects[idx].ray_intersect_at(orig, dir, time))
+    }
+
+    /// Fraction of the ambient light reaching `hit`, 1 when ambient occlusion is off.
+    fn ambient_visibility(&self, hit: Vector3<f32>, normal: Vector3<f32>, time: f32) -> f32 {
+        if self.ao_samples == 0 || (self.ambient == [0.0; 3] && self.ambient_hemisphere.is_none()) {
+            return 1.0;
+        }
+        // seeded by the hit itself, so that the same point always gets the same rays
+        let seed = hit.iter().fold(0u64, |acc, c| acc.rotate_left(21) ^ u64::from(c.to_bits()));
+        let mut sampler = Sampler::new(seed);
+        let occluded = (0..self.ao_samples)
+            .filter(|_| {
+                let dir = sampler.cosine_hemisphere(normal);
+                self.occluded(hit, normal, dir, self.ao_radius, time)
+            })
+            .count();
+        1.0 - occluded as f32 / self.ao_samples as f32
+    }
+
+    /// Color of the fog a ray scatters in before travelling `max_dist`, if any.
+    fn scatter_in_fog(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        max_dist: f32,
+        sampler: &mut Sampler,
+    ) -> Option<[f32; 3]> {
+        let dir = dir.normalize();
+        self.volumes
             .iter()
-            .filter_map(move |object| object.ray_intersect(orig, dir))
-            .collect();
-        intersections.sort_unstable_by(|a, b| b.dist.partial_cmp(&a.dist).unwrap());
-        intersections.pop()
+            .filter_map(|volume| {
+                let dist = volume.scatter_distance(orig, dir, max_dist, sampler)?;
+                Some((dist, volume.color()))
+            })
+            .fold(None, |nearest: Option<(f32, [f32; 3])>, (dist, color)| match nearest {
+                Some((best, _)) if best <= dist => nearest,
+                _ => Some((dist, color)),
+            })
+            .map(|(_, color)| color)
+    }
+
+    /// Whether anything blocks the way from `hit` to a light `light_dist` away in `light_dir`.
+    fn occluded(
+        &self,
+        hit: Vector3<f32>,
+        normal: Vector3<f32>,
+        light_dir: Vector3<f32>,
+        light_dist: f32,
+        time: f32,
+    ) -> bool {
+        let shadow_orig = offset_origin(hit, normal, light_dir, self.bias);
+        match self.test_intersect(shadow_orig, light_dir, time) {
+            Some(shadow_info) => shadow_info.dist < light_dist,
+            None => false,
+        }
     }
 
     pub fn cast_ray(
@@ -58,138 +648,1442 @@ impl Scene {
         dir: Vector3<f32>,
         recursion_limit: u32,
     ) -> [f32; 3] {
-        if recursion_limit == 0 { None } else { Some(()) }
-            .and_then(|_| self.test_intersect(orig, dir))
+        let path = Path::new(recursion_limit, 0.0);
+        self.trace(orig, dir, path, &self.background, &mut Sampler::new(0))
+    }
+
+    /// Like `cast_ray`, but `None` when the ray escapes to the background.
+    pub fn cast_ray_hit(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        recursion_limit: u32,
+    ) -> Option<[f32; 3]> {
+        let path = Path::new(recursion_limit, 0.0);
+        self.trace_hit(orig, dir, path, &self.background, &mut Sampler::new(0))
+    }
+
+    /// Light arriving along a ray with global illumination, following a single random path of
+    /// up to `max_bounces` bounces.
+    pub fn cast_ray_gi(&self, orig: Vector3<f32>, dir: Vector3<f32>, max_bounces: u32) -> [f32; 3] {
+        let path = Path::new(max_bounces, 0.0);
+        self.trace_gi(orig, dir, path, &self.background, &mut Sampler::new(0))
+            .unwrap_or_else(|| self.background.color(dir))
+    }
+
+    fn trace(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        path: Path,
+        background: &Background,
+        sampler: &mut Sampler,
+    ) -> [f32; 3] {
+        self.trace_hit(orig, dir, path, background, sampler)
+            .unwrap_or_else(|| background.color(dir))
+    }
+
+    /// `trace` for a secondary ray continuing `path`, already scaled by its `weight`.
+    fn trace_secondary(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        path: Path,
+        weight: f32,
+        background: &Background,
+        sampler: &mut Sampler,
+    ) -> Vector3<f32> {
+        let path = path.bounce(weight);
+        let survival = if self.russian_roulette && path.bounces >= ROULETTE_MIN_BOUNCES {
+            f32::min(path.throughput, 1.0)
+        } else {
+            1.0
+        };
+        if survival < 1.0 && sampler.next_f32() >= survival {
+            return nalgebra::zero();
+        }
+        let path = Path { throughput: path.throughput / survival, ..path };
+        Vector3::from(self.trace(orig, dir, path, background, sampler)) * (weight / survival)
+    }
+
+    fn trace_hit(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        path: Path,
+        background: &Background,
+        sampler: &mut Sampler,
+    ) -> Option<[f32; 3]> {
+        if path.remaining == 0 {
+            return None;
+        }
+        let nearest = self.test_intersect(orig, dir, path.time);
+        // fog in front of the surface may scatter the ray before it gets there
+        let surface_dist = nearest.as_ref().map_or(f32::INFINITY, |info| info.dist);
+        if let Some(color) = self.scatter_in_fog(orig, dir, surface_dist, sampler) {
+            return Some(color);
+        }
+        nearest
             .map(|info| {
                 let dir = dir.normalize();
+                // objects report outward normals, but shading wants the side the ray came from
+                let front_face = info.normal.dot(&dir).is_sign_negative();
+                let normal = if front_face { info.normal } else { -info.normal };
+                let normal = match &info.material.normal_map {
+                    Some(normal_map) => {
+                        perturb_normal(normal, info.tangent, normal_map.sample(info.uv))
+                    },
+                    None => normal,
+                };
+                let info = IntersectionInfo { normal, ..info };
+                // indices of refraction on the incoming and the far side of the surface
+                let (index_in, index_out) = match info.material.refract {
+                    Some(Refract { index, .. }) if front_face => (AIR_REFRACTION_INDEX, index),
+                    Some(Refract { index, .. }) => (index, AIR_REFRACTION_INDEX),
+                    None => (AIR_REFRACTION_INDEX, AIR_REFRACTION_INDEX),
+                };
                 let filtered_lights: Vec<_> = self
                     .lights
                     .iter()
                     .filter_map(|light| {
-                        let raw_light_dir = light.position - info.hit;
-                        let light_dir = raw_light_dir.normalize();
-                        let light_dist = raw_light_dir.norm();
+                        let (light_dir, light_dist, intensity) = light.illuminate(info.hit);
 
-                        let shadow_orig = if light_dir.dot(&info.normal).is_sign_negative() {
-                            info.hit - info.normal * 1e-3
-                        } else {
-                            info.hit + info.normal * 1e-3
+                        let visibility = match light.kind {
+                            _ if !light.casts_shadows => 1.0,
+                            LightKind::Area { corner, edges, samples } => {
+                                // fraction of random points on the emitter in plain sight
+                                let visible = (0..samples)
+                                    .filter(|_| {
+                                        let (s, t) = (sampler.next_f32(), sampler.next_f32());
+                                        let raw_dir = corner + edges.0 * s + edges.1 * t - info.hit;
+                                        let dist = raw_dir.norm();
+                                        let light_dir = raw_dir / dist;
+                                        !self.occluded(info.hit, info.normal, light_dir, dist, path.time)
+                                    })
+                                    .count();
+                                visible as f32 / samples as f32
+                            },
+                            _ if self.occluded(
+                                info.hit,
+                                info.normal,
+                                light_dir,
+                                light_dist,
+                                path.time,
+                            ) => 0.0,
+                            _ => 1.0,
                         };
-                        let shadow_info = self.test_intersect(shadow_orig, light_dir);
-                        match &shadow_info {
-                            Some(shadow_info) if shadow_info.dist < light_dist => None,
-                            _ => Some((light_dir, light)),
+                        // shadowed points still get the tinted part of the light
+                        let transmitted = Vector3::repeat(visibility)
+                            + Vector3::from(light.shadow_color) * (1.0 - visibility);
+                        if transmitted.max() > 0.0 {
+                            Some((light_dir, intensity.component_mul(&transmitted)))
+                        } else {
+                            None
                         }
                     })
                     .collect();
 
                 let diffuse_color_vec =
-                    if let Some(Diffuse { kind, albedo }) = &info.material.diffuse {
-                        let diffuse_intensity: f32 = filtered_lights
+                    if let Some(diffuse) = &info.material.diffuse {
+                        let diffuse_intensity = filtered_lights
                             .iter()
-                            .map(|(light_dir, light)| {
-                                light.intensity * f32::max(0.0, light_dir.dot(&info.normal))
+                            .map(|(light_dir, intensity)| {
+                                let lambert = f32::max(0.0, light_dir.dot(&info.normal));
+                                let rough = diffuse.oren_nayar(info.normal, -dir, *light_dir);
+                                *intensity * (lambert * rough)
                             })
-                            .sum();
-                        let raw_diffuse_color = match kind {
-                            DiffuseKind::Color(diffuse) => diffuse.clone(),
-                        };
-                        Vector3::from(raw_diffuse_color) * diffuse_intensity * *albedo
+                            .fold(nalgebra::zero(), |acc: Vector3<f32>, c| acc + c);
+                        let diffuse_color = Vector3::from(diffuse.color(info.uv, info.hit));
+                        let ambient_color = diffuse_color.component_mul(&self.ambient_light(info.normal))
+                            * self.ambient_visibility(info.hit, info.normal, path.time);
+                        (diffuse_color.component_mul(&diffuse_intensity) + ambient_color)
+                            * diffuse.albedo
                     } else {
                         nalgebra::zero()
                     };
                 let specular_color_vec =
                     if let Some(Specular { specular_exp, albedo }) = info.material.specular {
-                        let specular_intensity: f32 = filtered_lights
+                        let specular_intensity = filtered_lights
                             .iter()
-                            .map(|(light_dir, light)| {
-                                let reflect_dir = reflect(light_dir.clone(), info.normal);
+                            .map(|(light_dir, intensity)| {
+                                let reflect_dir = reflect(*light_dir, info.normal);
                                 let angle = f32::max(0.0, reflect_dir.dot(&dir));
-                                light.intensity * f32::powf(angle, specular_exp)
+                                *intensity * f32::powf(angle, specular_exp)
+                            })
+                            .fold(nalgebra::zero(), |acc: Vector3<f32>, c| acc + c);
+                        specular_intensity * albedo
+                    } else {
+                        nalgebra::zero()
+                    };
+                let pbr_color_vec =
+                    if let Some(pbr) = &info.material.pbr {
+                        filtered_lights
+                            .iter()
+                            .map(|(light_dir, intensity)| {
+                                pbr.shade(info.normal, -dir, *light_dir).component_mul(intensity)
                             })
-                            .sum();
-                        Vector3::from([1.0, 1.0, 1.0]) * specular_intensity * albedo
+                            .fold(nalgebra::zero(), |acc: Vector3<f32>, c| acc + c)
                     } else {
                         nalgebra::zero()
                     };
+                let refraction = info
+                    .material
+                    .refract
+                    .map(|_| refract_fresnel(dir, info.normal, index_in, index_out));
+                // dielectrics split the light between reflection and refraction by angle
+                let (reflect_weight, refract_weight) = match (info.material.reflect, refraction) {
+                    (Some(_), Some((_, reflectance))) => (reflectance, 1.0 - reflectance),
+                    _ => (1.0, 1.0),
+                };
                 let reflect_color_vec =
                     if let Some(albedo_reflect) = info.material.reflect {
                         let reflect_dir = reflect(dir, info.normal);
-                        let reflect_orig = if reflect_dir.dot(&info.normal).is_sign_negative() {
-                            info.hit - info.normal * 1e-3
+                        let reflect_orig =
+                            offset_origin(info.hit, info.normal, reflect_dir, self.bias);
+                        let roughness = info.material.reflect_roughness;
+                        let weight = albedo_reflect * reflect_weight;
+                        if roughness > 0.0 {
+                            // spread the lobe over several rays only where the path starts, so
+                            // that nested glossy bounces don't multiply the ray count
+                            let glossy_samples =
+                                if path.bounces == 0 { GLOSSY_SAMPLES } else { 1 };
+                            (0..glossy_samples)
+                                .map(|_| {
+                                    let glossy_dir = jitter_dir(reflect_dir, roughness, sampler);
+                                    self.trace_secondary(
+                                        reflect_orig,
+                                        glossy_dir,
+                                        path,
+                                        weight / glossy_samples as f32,
+                                        background,
+                                        sampler,
+                                    )
+                                })
+                                .fold(nalgebra::zero(), |acc: Vector3<f32>, c| acc + c)
                         } else {
-                            info.hit + info.normal * 1e-3
-                        };
-                        let raw_reflect_color = self.cast_ray(
-                            reflect_orig,
-                            reflect_dir,
-                            recursion_limit - 1,
-                        );
-                        Vector3::from(raw_reflect_color) * albedo_reflect
+                            self.trace_secondary(
+                                reflect_orig,
+                                reflect_dir,
+                                path,
+                                weight,
+                                background,
+                                sampler,
+                            )
+                        }
                     } else {
                         nalgebra::zero()
                     };
                 let refract_color_vec =
-                    if let Some(Refract { index, albedo }) = info.material.refract {
-                        let refract_dir = refract(dir, info.normal, AIR_REFRACTION_INDEX, index);
-                        let refract_orig = if refract_dir.dot(&info.normal).is_sign_negative() {
-                            info.hit - info.normal * 1e-3
+                    if let (Some(Refract { albedo, dispersion, .. }), Some((refract_dir, _))) =
+                        (info.material.refract, refraction)
+                    {
+                        let weight = albedo * refract_weight;
+                        match dispersion {
+                            Some(indices) => {
+                                // each channel bends by its own index, so split the path
+                                // into one per channel; a path already split follows its own
+                                let mut trace_channel = |channel: usize| {
+                                    let index = indices[channel];
+                                    let (index_in, index_out) = if front_face {
+                                        (AIR_REFRACTION_INDEX, index)
+                                    } else {
+                                        (index, AIR_REFRACTION_INDEX)
+                                    };
+                                    let channel_dir =
+                                        refract(dir, info.normal, index_in, index_out);
+                                    let channel_orig =
+                                        offset_origin(info.hit, info.normal, channel_dir, self.bias);
+                                    self.trace_secondary(
+                                        channel_orig,
+                                        channel_dir,
+                                        Path { channel: Some(channel), ..path },
+                                        weight,
+                                        background,
+                                        sampler,
+                                    )
+                                };
+                                match path.channel {
+                                    Some(channel) => trace_channel(channel),
+                                    None => Vector3::new(
+                                        trace_channel(0).x,
+                                        trace_channel(1).y,
+                                        trace_channel(2).z,
+                                    ),
+                                }
+                            },
+                            None => {
+                                let refract_orig =
+                                    offset_origin(info.hit, info.normal, refract_dir, self.bias);
+                                self.trace_secondary(
+                                    refract_orig,
+                                    refract_dir,
+                                    path,
+                                    weight,
+                                    background,
+                                    sampler,
+                                )
+                            },
+                        }
+                    } else {
+                        nalgebra::zero()
+                    };
+                // emitted light doesn't depend on the lights, nor on whether they're shadowed
+                let emission_color_vec =
+                    if let Some(emission) = info.material.emission {
+                        Vector3::from(emission)
+                    } else {
+                        nalgebra::zero()
+                    };
+                // the coat takes its share of the light off the top, leaving the rest to the base
+                let (clearcoat_weight, clearcoat_color_vec) =
+                    if let Some(Clearcoat { strength, roughness }) = info.material.clearcoat {
+                        let cos_i = f32::max(0.0, -dir.dot(&info.normal));
+                        let weight = strength
+                            * schlick(cos_i, AIR_REFRACTION_INDEX / CLEARCOAT_REFRACTION_INDEX);
+                        // Blinn-Phong exponent matching a microfacet lobe of this roughness
+                        let shininess = 2.0 / f32::max(roughness * roughness, 1e-4) - 2.0;
+                        let highlight = filtered_lights
+                            .iter()
+                            .map(|(light_dir, intensity)| {
+                                let half = (light_dir - dir).normalize();
+                                let angle = f32::max(0.0, half.dot(&info.normal));
+                                *intensity * f32::powf(angle, shininess)
+                            })
+                            .fold(nalgebra::zero(), |acc: Vector3<f32>, c| acc + c);
+                        let reflection = if weight > 0.0 {
+                            let coat_dir = reflect(dir, info.normal);
+                            let coat_orig =
+                                offset_origin(info.hit, info.normal, coat_dir, self.bias);
+                            self.trace_secondary(
+                                coat_orig,
+                                coat_dir,
+                                path,
+                                weight,
+                                background,
+                                sampler,
+                            )
                         } else {
-                            info.hit + info.normal * 1e-3
+                            nalgebra::zero()
                         };
-                        let raw_refract_color = self.cast_ray(
-                            refract_orig,
-                            refract_dir,
-                            recursion_limit - 1,
-                        );
-                        Vector3::from(raw_refract_color) * albedo
+                        (weight, highlight * weight + reflection)
                     } else {
-                        nalgebra::zero()
+                        (0.0, nalgebra::zero())
                     };
-                let mut color_vec =
+                let base_color_vec =
                     diffuse_color_vec +
                     specular_color_vec +
+                    pbr_color_vec +
                     reflect_color_vec +
                     refract_color_vec;
+                let mut color_vec =
+                    base_color_vec * (1.0 - clearcoat_weight) +
+                    clearcoat_color_vec +
+                    emission_color_vec;
+                if let Some(Refract { absorption: Some(absorption), .. }) = info.material.refract {
+                    // hitting the surface from the inside, so everything seen here has travelled
+                    // `dist` through the medium (Beer-Lambert)
+                    if !front_face {
+                        let transmittance =
+                            Vector3::from(absorption).map(|a| f32::exp(-a * info.dist));
+                        color_vec.component_mul_assign(&transmittance);
+                    }
+                }
                 let max = color_vec.max();
-                if max > 1.0 {
+                if !self.hdr && max > 1.0 {
                     color_vec /= max;
                 }
                 color_vec.into()
             })
-            .unwrap_or([0.2, 0.7, 0.8])
     }
 
-    pub fn render(
+    /// Path traced counterpart of `trace_hit`, `None` when the ray escapes without bouncing.
+    fn trace_gi(
+        &self,
+        orig: Vector3<f32>,
+        dir: Vector3<f32>,
+        path: Path,
+        background: &Background,
+        sampler: &mut Sampler,
+    ) -> Option<[f32; 3]> {
+        let mut orig = orig;
+        let mut dir = dir.normalize();
+        let mut throughput: Vector3<f32> = Vector3::repeat(1.0);
+        let mut radiance: Vector3<f32> = nalgebra::zero();
+        for bounce in 0..path.remaining {
+            let info = match self.test_intersect(orig, dir, path.time) {
+                Some(info) => info,
+                None if bounce == 0 => return None,
+                None => {
+                    radiance += throughput.component_mul(&Vector3::from(background.color(dir)));
+                    break;
+                },
+            };
+            let front_face = info.normal.dot(&dir).is_sign_negative();
+            let normal = if front_face { info.normal } else { -info.normal };
+            let normal = match &info.material.normal_map {
+                Some(normal_map) => {
+                    perturb_normal(normal, info.tangent, normal_map.sample(info.uv))
+                },
+                None => normal,
+            };
+            let material = &info.material;
+            if let Some(emission) = material.emission {
+                radiance += throughput.component_mul(&Vector3::from(emission));
+            }
+
+            // pick one way to scatter, weighting by the chance of picking it to stay unbiased
+            let reflect_chance = material.reflect.map_or(0.0, |albedo| f32::min(albedo, 1.0));
+            let diffuse_color = match (&material.diffuse, &material.pbr) {
+                (Some(diffuse), _) => {
+                    Some(Vector3::from(diffuse.color(info.uv, info.hit)) * diffuse.albedo)
+                },
+                (None, Some(pbr)) => Some(Vector3::from(pbr.base_color)),
+                (None, None) => None,
+            };
+            let next_dir = if let Some(Refract { index, albedo, .. }) = material.refract {
+                let (index_in, index_out) = if front_face {
+                    (AIR_REFRACTION_INDEX, index)
+                } else {
+                    (index, AIR_REFRACTION_INDEX)
+                };
+                let (refract_dir, reflectance) =
+                    refract_fresnel(dir, normal, index_in, index_out);
+                if sampler.next_f32() < reflectance {
+                    reflect(dir, normal)
+                } else {
+                    throughput *= albedo;
+                    refract_dir
+                }
+            } else if sampler.next_f32() < reflect_chance {
+                let reflect_dir = reflect(dir, normal);
+                if material.reflect_roughness > 0.0 {
+                    jitter_dir(reflect_dir, material.reflect_roughness, sampler)
+                } else {
+                    reflect_dir
+                }
+            } else if let Some(diffuse_color) = diffuse_color {
+                throughput = throughput.component_mul(&diffuse_color) / (1.0 - reflect_chance);
+                sampler.cosine_hemisphere(normal)
+            } else {
+                break;
+            };
+
+            if bounce + 1 >= ROULETTE_MIN_BOUNCES {
+                // dim paths carry little light, so end most of them early
+                let survival = f32::min(throughput.max(), 0.95);
+                if sampler.next_f32() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+            orig = offset_origin(info.hit, normal, next_dir, self.bias);
+            dir = next_dir;
+        }
+
+        let max = radiance.max();
+        if !self.hdr && max > 1.0 {
+            radiance /= max;
+        }
+        Some(radiance.into())
+    }
+
+    /// Renders with `settings`, averaging an `aa_samples` by `aa_samples` grid of jittered rays
+    /// per pixel. A single sample shoots through the pixel center. The framebuffer's alpha is set
+    /// to the fraction of rays hitting an object.
+    pub fn render(&self, fb: &mut Framebuffer, settings: &RenderSettings) -> Framebuffer {
+        self.render_with_progress(fb, settings, |_| {})
+    }
+
+    /// Renders only the background as seen by `camera`, one ray through the center of each
+    /// pixel, without testing any objects. For quickly previewing skies and environment maps;
+    /// the result is what `render` gives for an empty scene without antialiasing.
+    pub fn render_environment(&self, settings: &RenderSettings, camera: &Camera) -> Framebuffer {
+        let RenderSettings { width, height, .. } = *settings;
+        let background = settings.background.as_ref().unwrap_or(&self.background);
+        let mut fb = Framebuffer::new(width, height);
+        for (rc, pixel) in fb.buf_mut().iter_mut().enumerate() {
+            let (r, c) = (rc / width, rc % width);
+            let (_, dir) = camera.primary_ray(c as f32 + 0.5, r as f32 + 0.5, width, height);
+            *pixel = background.color(dir);
+        }
+        fb.set_alpha(Some(vec![0.0; width * height]));
+        fb
+    }
+
+    /// Like `render`, calling `progress` with the fraction of tiles done each time one finishes.
+    /// Tiles finish in parallel, so calls come from any thread and may be slightly out of order;
+    /// the last one to arrive isn't necessarily the one reporting 1.
+    ///
+    /// Without the `parallel` feature, tiles render one after another on the calling thread.
+    pub fn render_with_progress<F: Fn(f32) + Sync>(
         &self,
         fb: &mut Framebuffer,
-        width: usize,
-        height: usize,
-        fov: f32,
+        settings: &RenderSettings,
+        progress: F,
     ) -> Framebuffer {
+        self.render_tiles(fb, settings, progress).0
+    }
+
+    /// Like `render`, also telling whether the ray budget or the time limit of `settings` ran
+    /// out. Pixels that weren't rendered by then are left black and fully transparent.
+    pub fn render_bounded(
+        &self,
+        fb: &mut Framebuffer,
+        settings: &RenderSettings,
+    ) -> (Framebuffer, bool) {
+        self.render_tiles(fb, settings, |_| {})
+    }
+
+    fn render_tiles<F: Fn(f32) + Sync>(
+        &self,
+        fb: &mut Framebuffer,
+        settings: &RenderSettings,
+        progress: F,
+    ) -> (Framebuffer, bool) {
+        use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+        #[cfg(feature = "parallel")]
         use rayon::prelude::*;
 
-        let wf = width as f32;
-        let hf = height as f32;
-        let fov_half = fov / 2.0;
-        let fov_half_tan = f32::tan(fov_half);
-
-        let old = fb.render_with(|| {
-            (0..(width * height))
-                .into_par_iter()
-                .map(|rc| {
-                    let r = rc / width;
-                    let c = rc % width;
-                    let rf = r as f32;
-                    let cf = c as f32;
-                    let dir_x = (cf + 0.5) - wf / 2.0;
-                    let dir_y = -(rf + 0.5) + hf / 2.0;
-                    let dir_z = -hf / (2.0 * fov_half_tan);
-                    let dir = Vector3::from([dir_x, dir_y, dir_z]);
-                    self.cast_ray(nalgebra::zero(), dir, 4)
-                })
-                .collect()
+        let RenderSettings { width, height, aa_samples, .. } = *settings;
+        let camera = match &self.camera {
+            Some(camera) => camera.clone(),
+            None => Camera::new(nalgebra::zero(), -Vector3::z(), Vector3::y(), settings.fov),
+        };
+        let max_depth = settings.max_depth.unwrap_or(self.max_depth);
+        let paths = match settings.mode {
+            RenderMode::Whitted => 1,
+            RenderMode::PathTraced { samples } => u32::max(samples, 1),
+        };
+        let background = settings.background.as_ref().unwrap_or(&self.background);
+
+        let rays_cast = AtomicU64::new(0);
+        let deadline = settings.time_limit.map(|limit| std::time::Instant::now() + limit);
+        let truncated = AtomicBool::new(false);
+        // checked before each pixel, so a pixel in progress may still overshoot the budget
+        let out_of_budget = || {
+            if truncated.load(Ordering::Relaxed) {
+                return true;
+            }
+            let over_budget = match settings.ray_budget {
+                Some(budget) => rays_cast.load(Ordering::Relaxed) >= budget,
+                None => false,
+            };
+            let over_time = match deadline {
+                Some(deadline) => std::time::Instant::now() >= deadline,
+                None => false,
+            };
+            if over_budget || over_time {
+                truncated.store(true, Ordering::Relaxed);
+            }
+            over_budget || over_time
+        };
+
+        // color summed over the camera and path samples through one point of a pixel, and how
+        // many of them hit something
+        let rays_per_point = camera.samples() * paths;
+        let trace_point = |x: f32, y: f32, sampler: &mut Sampler| {
+            let mut sum: Vector3<f32> = nalgebra::zero();
+            let mut hits = 0;
+            for _ in 0..camera.samples() {
+                let (orig, dir) = camera.sample_ray(x, y, width, height, sampler);
+                for _ in 0..paths {
+                    let time = match settings.shutter {
+                        Some((open, close)) => open + (close - open) * sampler.next_f32(),
+                        None => 0.0,
+                    };
+                    let path = Path::new(max_depth, time);
+                    let hit = match settings.mode {
+                        RenderMode::Whitted => self.trace_hit(orig, dir, path, background, sampler),
+                        RenderMode::PathTraced { .. } => {
+                            self.trace_gi(orig, dir, path, background, sampler)
+                        },
+                    };
+                    if hit.is_some() {
+                        hits += 1;
+                    }
+                    let color = hit.unwrap_or_else(|| background.color(dir));
+                    sum += Vector3::from(color);
+                }
+            }
+            (sum, hits)
+        };
+
+        let shade_pixel = |rc: usize| {
+            let r = rc / width;
+            let c = rc % width;
+            let rf = r as f32;
+            let cf = c as f32;
+            // seeded by pixel, so that renders are reproducible
+            let mut sampler = Sampler::for_pixel(c as u32, r as u32, 0);
+            let mut sum: Vector3<f32> = nalgebra::zero();
+            let mut hits = 0;
+            let points = match settings.adaptive {
+                Some(AdaptiveSampling { min_samples, max_samples, threshold }) => {
+                    let min_samples = u32::max(min_samples, 2);
+                    let max_samples = u32::max(max_samples, min_samples);
+                    // running mean and variance of the luminance (Welford)
+                    let (mut mean, mut m2) = (0.0, 0.0);
+                    let mut n = 0;
+                    while n < max_samples {
+                        if n >= min_samples {
+                            let std_error = f32::sqrt(m2 / (n - 1) as f32 / n as f32);
+                            if std_error <= threshold {
+                                break;
+                            }
+                        }
+                        let (dx, dy) = (sampler.next_f32(), sampler.next_f32());
+                        let (point_sum, point_hits) = trace_point(cf + dx, rf + dy, &mut sampler);
+                        sum += point_sum;
+                        hits += point_hits;
+                        n += 1;
+                        let lum = luminance((point_sum / rays_per_point as f32).into());
+                        let delta = lum - mean;
+                        mean += delta / n as f32;
+                        m2 += delta * (lum - mean);
+                    }
+                    n
+                },
+                None => {
+                    let grid = u32::max(aa_samples, 1);
+                    for cell in 0..(grid * grid) {
+                        let (dx, dy) = if grid == 1 {
+                            (0.5, 0.5)
+                        } else {
+                            // stratified, one random point in each cell of the grid
+                            let gf = grid as f32;
+                            let cell_x = (cell % grid) as f32 + sampler.next_f32();
+                            let cell_y = (cell / grid) as f32 + sampler.next_f32();
+                            (cell_x / gf, cell_y / gf)
+                        };
+                        let (point_sum, point_hits) = trace_point(cf + dx, rf + dy, &mut sampler);
+                        sum += point_sum;
+                        hits += point_hits;
+                    }
+                    grid * grid
+                },
+            };
+            let samples = points * rays_per_point;
+            rays_cast.fetch_add(u64::from(samples), Ordering::Relaxed);
+            let color: [f32; 3] = (sum / samples as f32).into();
+            (color, hits as f32 / samples as f32)
+        };
+        let tile_size = usize::max(settings.tile_size, 1);
+        let tiles_x = width.div_ceil(tile_size);
+        let tiles_y = height.div_ceil(tile_size);
+        let tile_count = tiles_x * tiles_y;
+        let finished_tiles = AtomicUsize::new(0);
+        // pixels are seeded by their coordinates, so the tiling doesn't change the image
+        #[cfg(feature = "parallel")]
+        let tile_indices = (0..tile_count).into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let tile_indices = 0..tile_count;
+        let tiles: Vec<Vec<_>> = tile_indices
+            .map(|tile| {
+                let x0 = (tile % tiles_x) * tile_size;
+                let y0 = (tile / tiles_x) * tile_size;
+                let x1 = usize::min(x0 + tile_size, width);
+                let y1 = usize::min(y0 + tile_size, height);
+                let pixels: Vec<_> = (y0..y1)
+                    .flat_map(|r| (x0..x1).map(move |c| r * width + c))
+                    .filter(|_| !out_of_budget())
+                    .map(|rc| (rc, shade_pixel(rc)))
+                    .collect();
+                let finished = finished_tiles.fetch_add(1, Ordering::Relaxed) + 1;
+                progress(finished as f32 / tile_count as f32);
+                pixels
+            })
+            .collect();
+        let mut colors = vec![[0.0; 3]; width * height];
+        let mut alpha = vec![0.0; width * height];
+        for (rc, (color, coverage)) in tiles.into_iter().flatten() {
+            colors[rc] = color;
+            alpha[rc] = coverage;
+        }
+        let old = fb.render_with(|| colors);
+        fb.set_alpha(Some(alpha));
+        (std::mem::replace(fb, old), truncated.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Material,
+        object::{Cone, Moving, Plane, Quad, Sphere, Triangle},
+    };
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut scene = Scene::new();
+        let mut sampler = Sampler::new(7);
+        for _ in 0..200 {
+            let center = Vector3::new(
+                sampler.next_f32() * 20.0 - 10.0,
+                sampler.next_f32() * 20.0 - 10.0,
+                -10.0 - sampler.next_f32() * 20.0,
+            );
+            scene.push_object(Sphere::new(center, 0.2 + sampler.next_f32(), Material::none()));
+        }
+        scene.push_object(Plane::new(Vector3::new(0.0, -12.0, 0.0), Vector3::y(), Material::none()));
+
+        let camera = Camera::new(nalgebra::zero(), -Vector3::z(), Vector3::y(), 1.2);
+        for y in 0..48 {
+            for x in 0..64 {
+                let (orig, dir) = camera.primary_ray(x as f32 + 0.5, y as f32 + 0.5, 64, 48);
+                let dir = dir.normalize();
+                let brute_force = scene
+                    .objects
+                    .iter()
+                    .filter_map(|object| object.ray_intersect(orig, dir))
+                    .fold(None, |nearest: Option<f32>, info| match nearest {
+                        Some(best) if best <= info.dist => nearest,
+                        _ => Some(info.dist),
+                    });
+                let bvh = scene.test_intersect(orig, dir, 0.0).map(|info| info.dist);
+                match (bvh, brute_force) {
+                    // the ray gets normalized once more on the way in, so allow for rounding
+                    (Some(a), Some(b)) => assert!((a - b).abs() < 1e-4 * b, "pixel ({}, {})", x, y),
+                    (a, b) => assert_eq!(a, b, "pixel ({}, {})", x, y),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn back_face_is_lit() {
+        let mut scene = Scene::new();
+        // wound to face +z, seen and lit from -z
+        scene.push_object(Triangle::new(
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Material::color([1.0, 1.0, 1.0], 1.0),
+        ));
+        scene.push_light(Light::new(Vector3::new(0.0, 0.0, -5.0), 1.0));
+
+        let color = scene.cast_ray(Vector3::new(0.0, 0.0, -5.0), Vector3::z(), 4);
+        assert!(color.iter().all(|&c| c > 0.5), "{:?}", color);
+    }
+
+    #[test]
+    fn move_light() {
+        let mut light = Light::colored(Vector3::new(1.0, 2.0, 3.0), [0.5, 1.0, 2.0]);
+        assert_eq!(light.position(), Vector3::new(1.0, 2.0, 3.0));
+        light.set_position(Vector3::new(-4.0, 5.0, 0.0));
+        assert_eq!(light.position(), Vector3::new(-4.0, 5.0, 0.0));
+        assert_eq!(light.intensity(), Vector3::new(0.5, 1.0, 2.0));
+        assert_eq!(light.max_intensity(), 2.0);
+        assert_eq!(Light::new(Vector3::new(1.0, 2.0, 3.0), 1.5).max_intensity(), 1.5);
+    }
+
+    #[test]
+    fn dispersion_matches_plain_refraction() {
+        // with the same index for every channel, splitting the path changes nothing, even with
+        // a background bright enough for the default clamping to kick in
+        let glass = Material::none().with_refract(1.5, 1.0);
+        let background = Background::Solid([2.0, 0.5, 0.25]);
+        let mut plain = Scene::new();
+        plain.set_background(background.clone());
+        plain.push_object(Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.0, glass.clone()));
+        let mut dispersive = Scene::new();
+        dispersive.set_background(background);
+        dispersive.push_object(Sphere::new(
+            Vector3::new(0.0, 0.0, -4.0),
+            1.0,
+            glass.with_dispersion([1.5; 3]),
+        ));
+
+        let dir = Vector3::new(0.1, 0.05, -1.0).normalize();
+        let expected = plain.cast_ray(nalgebra::zero(), dir, 4);
+        let color = dispersive.cast_ray(nalgebra::zero(), dir, 4);
+        for (a, b) in color.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-5, "{:?} != {:?}", color, expected);
+        }
+    }
+
+    #[test]
+    fn emissive_sphere_in_the_dark() {
+        let mut scene = Scene::new();
+        scene.set_background(Background::Solid([0.0; 3]));
+        let glow = Material::none().with_emission([0.2, 0.4, 0.6]);
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.0, glow));
+
+        let color = scene.cast_ray(nalgebra::zero(), -Vector3::z(), 4);
+        assert_eq!(color, [0.2, 0.4, 0.6]);
+    }
+
+    #[test]
+    fn quadratic_falloff_halves_at_root_two() {
+        let light = Light::with_falloff(nalgebra::zero(), 1.0, FalloffKind::Quadratic);
+        let (_, _, near) = light.illuminate(Vector3::new(1.0, 0.0, 0.0));
+        let (_, dist, far) = light.illuminate(Vector3::new(1.0, 1.0, 0.0));
+        assert!((dist - f32::sqrt(2.0)).abs() < 1e-5, "{}", dist);
+        assert!((far * 2.0 - near).norm() < 1e-5, "{} {}", near, far);
+
+        let constant = Light::new(nalgebra::zero(), 1.0);
+        let (_, _, far) = constant.illuminate(Vector3::new(10.0, 0.0, 0.0));
+        assert_eq!(far, Vector3::repeat(1.0));
+    }
+
+    #[test]
+    fn antialiasing_softens_silhouette() {
+        let mut scene = Scene::new();
+        scene.set_background(Background::Solid([0.0; 3]));
+        let glow = Material::none().with_emission([1.0; 3]);
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.0, glow));
+
+        let is_partial = |pixel: &[f32; 3]| pixel[0] > 0.01 && pixel[0] < 0.99;
+        let settings = RenderSettings { width: 16, height: 16, ..RenderSettings::default() };
+        let single = scene.render(&mut Framebuffer::new(16, 16), &settings);
+        assert!(!single.buf().iter().any(is_partial));
+
+        let settings = RenderSettings { aa_samples: 4, ..settings };
+        let smooth = scene.render(&mut Framebuffer::new(16, 16), &settings);
+        assert!(smooth.buf().iter().any(is_partial));
+    }
+
+    #[test]
+    fn remove_middle_object() {
+        let mut scene = Scene::new();
+        for x in 0..3 {
+            scene.push_object(Sphere::new(Vector3::new(x as f32, 0.0, -5.0), 0.25, Material::none()));
+        }
+        let removed = scene.remove_object(1);
+        assert_eq!(removed.bounding_box().unwrap().min().x, 0.75);
+        assert_eq!(scene.objects_len(), 2);
+
+        let centers: Vec<_> = scene
+            .objects
+            .iter()
+            .map(|object| object.bounding_box().unwrap().min().x + 0.25)
+            .collect();
+        assert_eq!(centers, [0.0, 2.0]);
+        // the acceleration structure follows along
+        assert!(scene.test_intersect(Vector3::new(1.0, 0.0, 0.0), -Vector3::z(), 0.0).is_none());
+        assert!(scene.test_intersect(Vector3::new(2.0, 0.0, 0.0), -Vector3::z(), 0.0).is_some());
+    }
+
+    #[test]
+    fn bounding_box_of_two_spheres() {
+        let mut scene = Scene::new();
+        assert!(scene.bounding_box().is_none());
+        scene.push_object(Plane::new(nalgebra::zero(), Vector3::y(), Material::none()));
+        assert!(scene.bounding_box().is_none());
+
+        scene.push_object(Sphere::new(Vector3::new(-2.0, 0.0, 0.0), 1.0, Material::none()));
+        scene.push_object(Sphere::new(Vector3::new(3.0, 1.0, -1.0), 0.5, Material::none()));
+        let bounds = scene.bounding_box().unwrap();
+        assert_eq!(bounds.min(), Vector3::new(-3.0, -1.0, -1.5));
+        assert_eq!(bounds.max(), Vector3::new(3.5, 1.5, 1.0));
+    }
+
+    /// Reports a hit at a NaN distance for every ray, like degenerate geometry might.
+    struct NanObject;
+
+    impl Object for NanObject {
+        fn ray_intersect(&self, orig: Vector3<f32>, dir: Vector3<f32>) -> Option<IntersectionInfo> {
+            Some(IntersectionInfo {
+                dist: f32::NAN,
+                hit: orig + dir * f32::NAN,
+                normal: Vector3::repeat(f32::NAN),
+                material: Material::none(),
+                uv: (0.0, 0.0),
+                tangent: Vector3::x(),
+            })
+        }
+    }
+
+    #[test]
+    fn nan_distance_is_ignored() {
+        let mut scene = Scene::new();
+        scene.push_object(NanObject);
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.0, Material::none()));
+        scene.push_light(Light::new(Vector3::new(0.0, 5.0, 0.0), 1.0));
+
+        let info = scene.test_intersect(nalgebra::zero(), -Vector3::z(), 0.0).unwrap();
+        assert_eq!(info.dist, 3.0);
+        let settings = RenderSettings { width: 8, height: 8, ..RenderSettings::default() };
+        scene.render(&mut Framebuffer::new(8, 8), &settings);
+    }
+
+    #[test]
+    fn cone_shadows_and_reflects() {
+        // a cone hanging point up above the floor, its capped base at y = 0
+        let cone = |material| {
+            Cone::new(Vector3::new(0.0, 1.0, -10.0), -Vector3::y(), 0.5, 1.0, material).with_cap()
+        };
+        let floor = |material| Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::y(), material);
+        let dir = Vector3::new(0.0, -1.0, -10.0).normalize();
+
+        let lit_floor = || {
+            let mut scene = Scene::new();
+            scene.push_object(floor(Material::color([1.0; 3], 1.0)));
+            scene.push_light(Light::new(Vector3::new(0.0, 10.0, -10.0), 1.0));
+            scene
+        };
+        let mut shadowed = lit_floor();
+        shadowed.push_object(cone(Material::color([1.0; 3], 1.0)));
+        let lit = lit_floor().cast_ray(nalgebra::zero(), dir, 4);
+        let shadowed = shadowed.cast_ray(nalgebra::zero(), dir, 4);
+        assert!(shadowed[0] < lit[0] * 0.5, "{:?} vs {:?}", shadowed, lit);
+
+        // the mirror floor bounces the ray up into the base of a red cone
+        let mut mirrored = Scene::new();
+        mirrored.set_background(Background::Solid([0.0; 3]));
+        mirrored.push_object(floor(Material::none().with_reflect(1.0)));
+        mirrored.push_object(cone(Material::none().with_emission([1.0, 0.0, 0.0])));
+        let color = mirrored.cast_ray(nalgebra::zero(), Vector3::new(0.0, -1.0, -5.0).normalize(), 4);
+        assert!(color[0] > 0.5 && color[1] < 0.01, "{:?}", color);
+    }
+
+    #[test]
+    fn sun_shadows_fall_the_same_way() {
+        let mut scene = Scene::new();
+        let floor = Material::color([1.0; 3], 1.0);
+        scene.push_object(Plane::new(nalgebra::zero(), Vector3::y(), floor));
+        for &x in &[-3.0, 3.0] {
+            scene.push_object(Sphere::new(Vector3::new(x, 1.0, -10.0), 1.0, Material::none()));
+        }
+        // shining down and to the right, so each shadow lands one unit right of its sphere
+        scene.push_light(Light::directional(Vector3::new(1.0, -1.0, 0.0), 1.0));
+        let floor_at = |x: f32| {
+            let orig = Vector3::new(0.0, 3.0, 0.0);
+            scene.cast_ray(orig, (Vector3::new(x, 0.0, -10.0) - orig).normalize(), 1)[0]
+        };
+        for &x in &[-3.0, 3.0] {
+            assert!(floor_at(x + 1.0) < 0.01, "{}", floor_at(x + 1.0));
+            assert!(floor_at(x - 1.0) > 0.5, "{}", floor_at(x - 1.0));
+        }
+    }
+
+    #[test]
+    fn spot_lights_a_soft_edged_disk() {
+        let mut scene = Scene::new();
+        let floor = Material::color([1.0; 3], 1.0);
+        scene.push_object(Plane::new(nalgebra::zero(), Vector3::y(), floor));
+        // 5 units up, so the full cone reaches about 1 unit out and the fade ends past 2
+        scene.push_light(Light::spot(Vector3::new(0.0, 5.0, -5.0), -Vector3::y(), 1.0, 0.2, 0.4));
+        let floor_at = |offset: Vector3<f32>| {
+            let orig = Vector3::new(0.0, 8.0, -5.0);
+            let target = Vector3::new(0.0, 0.0, -5.0) + offset;
+            scene.cast_ray(orig, (target - orig).normalize(), 1)[0]
+        };
+        // the same all the way around
+        for &axis in &[Vector3::x(), Vector3::z(), -Vector3::x()] {
+            let inside = floor_at(axis * 0.5);
+            assert!(inside > 0.95, "{}", inside);
+            let edge = floor_at(axis * 1.5);
+            assert!(edge > 0.05 && edge < 0.9, "{}", edge);
+            assert_eq!(floor_at(axis * 3.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn area_light_casts_a_penumbra() {
+        let mut scene = Scene::new();
+        let floor = Material::color([1.0; 3], 1.0);
+        scene.push_object(Plane::new(nalgebra::zero(), Vector3::y(), floor));
+        // a ceiling with its edge halfway between the floor and a 2 unit wide light, so the
+        // penumbra spans from x = -1 to 1
+        let ceiling = (Vector3::x() * 10.0, Vector3::z() * 10.0);
+        scene.push_object(Quad::new(Vector3::new(-10.0, 2.5, -10.0), ceiling, Material::none()));
+        let emitter = (Vector3::x() * 2.0, Vector3::z() * 2.0);
+        scene.push_light(Light::area(Vector3::new(-1.0, 5.0, -6.0), emitter, 1.0, 256));
+        let floor_at = |x: f32| {
+            let orig = Vector3::new(0.0, 1.0, 0.0);
+            scene.cast_ray(orig, (Vector3::new(x, 0.0, -5.0) - orig).normalize(), 1)[0]
+        };
+
+        assert!(floor_at(-1.5) < 0.01, "{}", floor_at(-1.5));
+        let partial: Vec<_> = [-0.5, 0.0, 0.5].iter().map(|&x| floor_at(x)).collect();
+        assert!(partial.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", partial);
+        assert!(partial[0] > 0.05 && partial[2] < floor_at(1.5) * 0.95, "{:?}", partial);
+    }
+
+    #[test]
+    fn ambient_tints_the_shadow() {
+        let shadowed_floor = |ambient: [f32; 3]| {
+            let mut scene = Scene::new();
+            scene.set_ambient(ambient);
+            let orange = Material::color([1.0, 0.5, 0.0], 1.0);
+            scene.push_object(Plane::new(nalgebra::zero(), Vector3::y(), orange));
+            scene.push_object(Sphere::new(Vector3::new(0.0, 2.0, -5.0), 1.0, Material::none()));
+            scene.push_light(Light::new(Vector3::new(0.0, 10.0, -5.0), 1.0));
+            let orig = Vector3::new(0.0, 1.0, 0.0);
+            scene.cast_ray(orig, (Vector3::new(0.0, 0.0, -5.0) - orig).normalize(), 1)
+        };
+        assert_eq!(shadowed_floor([0.0; 3]), [0.0; 3]);
+        let color = shadowed_floor([0.1; 3]);
+        let expected = [0.1, 0.05, 0.0];
+        assert!(color.iter().zip(&expected).all(|(c, e)| (c - e).abs() < 1e-5), "{:?}", color);
+    }
+
+    #[test]
+    fn red_light_on_white_sphere() {
+        let mut scene = Scene::new();
+        let white = Material::color([1.0; 3], 1.0);
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, white));
+        scene.push_light(Light::colored(Vector3::new(0.0, 0.0, 5.0), [1.0, 0.0, 0.0]));
+        let color = scene.cast_ray(nalgebra::zero(), -Vector3::z(), 1);
+        assert!(color[0] > 0.9 && color[1] == 0.0 && color[2] == 0.0, "{:?}", color);
+    }
+
+    #[test]
+    fn gradient_from_top_to_bottom() {
+        let (top, bottom) = ([0.0, 0.0, 1.0], [1.0, 1.0, 1.0]);
+        let mut scene = Scene::new();
+        scene.set_background(Background::Gradient { top, bottom });
+        assert_eq!(scene.cast_ray(nalgebra::zero(), Vector3::y(), 1), top);
+        assert_eq!(scene.cast_ray(nalgebra::zero(), -Vector3::y(), 1), bottom);
+
+        // bluer towards the top row of the image, whiter towards the bottom one
+        let settings = RenderSettings { width: 4, height: 9, ..RenderSettings::default() };
+        let image = scene.render(&mut Framebuffer::new(4, 9), &settings);
+        let column: Vec<_> = (0..9).map(|y| image.get_pixel(0, y)).collect();
+        assert!(column.windows(2).all(|pair| pair[0][0] < pair[1][0]), "{:?}", column);
+        assert!(column.iter().all(|pixel| pixel[2] == 1.0), "{:?}", column);
+    }
+
+    #[test]
+    fn environment_center_straight_ahead() {
+        let mut image = Framebuffer::new(3, 3);
+        image.set_pixel(1, 1, [1.0, 0.5, 0.0]);
+        let mut png = Vec::new();
+        image.write_png_with_gamma(&mut png, 1.0).unwrap();
+
+        let mut scene = Scene::new();
+        scene.set_background(Background::environment_from_png(&png[..]).unwrap());
+        let color = scene.cast_ray(nalgebra::zero(), Vector3::z(), 1);
+        let expected = [1.0, 0.5, 0.0];
+        // through 8 bits and back
+        assert!(color.iter().zip(&expected).all(|(c, e)| (c - e).abs() < 0.01), "{:?}", color);
+    }
+
+    #[test]
+    fn deeper_corridor_shows_more_reflections() {
+        let lit_pixels = |max_depth: u32| {
+            let mut scene = Scene::new();
+            scene.set_max_depth(max_depth);
+            scene.set_background(Background::Solid([0.0; 3]));
+            // a glowing ball between two mirrors, seen again in each bounce between them
+            let mirror = Material::none().with_reflect(1.0);
+            let sides = (Vector3::y() * 2.0, -Vector3::z() * 20.0);
+            for &x in &[-1.0, 1.0] {
+                scene.push_object(Quad::new(Vector3::new(x, -1.0, 0.0), sides, mirror.clone()));
+            }
+            let glow = Material::none().with_emission([1.0; 3]);
+            scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -6.0), 0.3, glow));
+            let settings = RenderSettings { width: 48, height: 32, ..RenderSettings::default() };
+            let image = scene.render(&mut Framebuffer::new(48, 32), &settings);
+            image.buf().iter().filter(|pixel| pixel[0] > 0.5).count()
+        };
+        let (shallow, deep) = (lit_pixels(1), lit_pixels(8));
+        assert!(shallow > 0 && deep > shallow * 3, "{} vs {}", shallow, deep);
+    }
+
+    #[test]
+    fn camera_inside_a_sphere() {
+        // from the center of a glass ball, rays leave head on and carry straight on
+        let mut scene = Scene::new();
+        scene.set_background(Background::Solid([0.0; 3]));
+        let glass = Material::none().with_refract(1.5, 1.0);
+        scene.push_object(Sphere::new(nalgebra::zero(), 2.0, glass));
+        let glow = Material::none().with_emission([1.0, 0.5, 0.0]);
+        scene.push_object(Sphere::new(Vector3::new(5.0, 0.0, 0.0), 0.5, glow));
+        let color = scene.cast_ray(nalgebra::zero(), Vector3::x(), 4);
+        assert!((color[0] - 1.0).abs() < 0.05 && (color[1] - 0.5).abs() < 0.05, "{:?}", color);
+        assert_eq!(scene.cast_ray(nalgebra::zero(), -Vector3::x(), 4), [0.0; 3]);
+
+        // a matte ball lit from within shades its inside, facing the ray
+        let mut scene = Scene::new();
+        scene.push_object(Sphere::new(nalgebra::zero(), 2.0, Material::color([1.0; 3], 1.0)));
+        scene.push_light(Light::new(Vector3::new(0.0, 1.0, 0.0), 1.0));
+        let color = scene.cast_ray(nalgebra::zero(), Vector3::y(), 4);
+        assert!(color.iter().all(|&c| c > 0.9), "{:?}", color);
+    }
+
+    #[test]
+    fn large_coordinates_need_more_bias() {
+        let dark_pixels = |bias: f32| {
+            let far = Vector3::new(1e5, 1e5, -1e5);
+            let mut scene = Scene::new();
+            scene.set_bias(bias);
+            scene.set_background(Background::Solid([1.0; 3]));
+            scene.push_object(Sphere::new(far, 50.0, Material::color([1.0; 3], 1.0)));
+            // lit from behind the camera, so all of the visible side faces the light
+            scene.push_light(Light::new(far + Vector3::new(0.0, 0.0, 1000.0), 1.0));
+            let eye = far + Vector3::new(0.0, 0.0, 200.0);
+            scene.set_camera(Camera::new(eye, far, Vector3::y(), 0.6));
+            let settings = RenderSettings { width: 32, height: 32, ..RenderSettings::default() };
+            let image = scene.render(&mut Framebuffer::new(32, 32), &settings);
+            image.buf().iter().filter(|pixel| pixel[0] < 0.05).count()
+        };
+        // at 1e5 floats are about 0.01 apart, too coarse for the default bias
+        assert!(dark_pixels(1e-3) > 0);
+        assert_eq!(dark_pixels(0.1), 0);
+    }
+
+    #[test]
+    fn default_settings_render_as_before() {
+        let mut scene = Scene::new();
+        let ivory = Material::color([0.4, 0.4, 0.3], 0.6).with_specular(50.0, 0.3);
+        let ivory = ivory.with_reflect(0.1);
+        scene.push_object(Sphere::new(Vector3::new(-3.0, 0.0, -16.0), 2.0, ivory));
+        let glass = Material::none().with_specular(125.0, 0.5).with_reflect(0.1);
+        let glass = glass.with_refract(1.5, 0.8);
+        scene.push_object(Sphere::new(Vector3::new(-1.0, -1.5, -12.0), 2.0, glass));
+        let floor = Material::default_diffuse();
+        scene.push_object(Plane::new(Vector3::new(0.0, -4.0, 0.0), Vector3::y(), floor));
+        scene.push_light(Light::new(Vector3::new(-20.0, 20.0, 20.0), 1.5));
+        let (width, height) = (32, 24);
+        let settings = RenderSettings { width, height, ..RenderSettings::default() };
+        let image = scene.render(&mut Framebuffer::new(width, height), &settings);
+
+        // the rays the fixed camera used to cast, one per pixel with a recursion limit of 4
+        let dir_z = -(height as f32) / (2.0 * f32::tan(settings.fov / 2.0));
+        for y in 0..height {
+            for x in 0..width {
+                let dir_x = (x as f32 + 0.5) - width as f32 / 2.0;
+                let dir_y = -(y as f32 + 0.5) + height as f32 / 2.0;
+                let dir = Vector3::new(dir_x, dir_y, dir_z).normalize();
+                let expected = scene.cast_ray(nalgebra::zero(), dir, 4);
+                let pixel = image.get_pixel(x, y);
+                let close = pixel.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-4);
+                assert!(close, "pixel ({}, {}): {:?} vs {:?}", x, y, pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn progress_reaches_one() {
+        let mut scene = Scene::new();
+        let ball = Material::default_diffuse();
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, ball));
+        let settings = RenderSettings {
+            width: 40,
+            height: 30,
+            tile_size: 8,
+            ..RenderSettings::default()
+        };
+        let seen = std::sync::Mutex::new(Vec::new());
+        scene.render_with_progress(&mut Framebuffer::new(40, 30), &settings, |fraction| {
+            seen.lock().unwrap().push(fraction);
         });
-        std::mem::replace(fb, old)
+
+        let seen = seen.into_inner().unwrap();
+        assert!(seen.len() > 1, "{:?}", seen);
+        assert!(seen.iter().all(|&fraction| fraction > 0.0 && fraction <= 1.0), "{:?}", seen);
+        let max = seen.iter().cloned().fold(0.0, f32::max);
+        assert!((max - 1.0).abs() < 1e-6, "{}", max);
+    }
+
+    #[test]
+    fn tiles_match_single_pixels() {
+        let mut scene = Scene::new();
+        let ball = Material::color([1.0, 0.5, 0.2], 1.0).with_reflect(0.2);
+        scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, ball));
+        let floor = Material::default_diffuse();
+        scene.push_object(Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::y(), floor));
+        // random shadow rays and antialiasing jitter, seeded per pixel rather than per tile
+        let emitter = (Vector3::x() * 2.0, Vector3::z() * 2.0);
+        scene.push_light(Light::area(Vector3::new(-1.0, 4.0, -6.0), emitter, 1.0, 4));
+
+        let render = |tile_size: usize| {
+            let settings = RenderSettings {
+                width: 30,
+                height: 20,
+                aa_samples: 4,
+                tile_size,
+                ..RenderSettings::default()
+            };
+            scene.render(&mut Framebuffer::new(30, 20), &settings)
+        };
+        let per_pixel = render(1);
+        for &tile_size in &[7, 32] {
+            assert_eq!(render(tile_size).buf(), per_pixel.buf(), "tile size {}", tile_size);
+        }
+    }
+
+    #[test]
+    fn occlusion_darkens_the_crevice() {
+        let mut scene = Scene::new();
+        scene.set_ambient([1.0; 3]);
+        scene.set_ambient_occlusion(64, 1.0);
+        // two balls touching at (0, 0, -5)
+        for &x in &[-1.0, 1.0] {
+            let ball = Material::color([1.0; 3], 1.0);
+            scene.push_object(Sphere::new(Vector3::new(x, 0.0, -5.0), 1.0, ball));
+        }
+        let towards = |target: Vector3<f32>| {
+            scene.cast_ray(nalgebra::zero(), target.normalize(), 1)[0]
+        };
+        let crevice = towards(Vector3::new(-0.1, 0.0, -4.8));
+        let exposed = towards(Vector3::new(-1.0, 0.0, -4.0));
+        assert!(crevice < exposed * 0.7, "{} vs {}", crevice, exposed);
+    }
+
+    #[test]
+    fn cornell_box_bleeds_and_converges() {
+        let mut scene = Scene::new();
+        scene.set_background(Background::Solid([0.0; 3]));
+        let white = Material::color([0.8; 3], 1.0);
+        // a closed box around the camera with a glowing ceiling, the only source of light for
+        // path tracing
+        let walls = [
+            (Vector3::new(-1.0, 0.0, 0.0), Vector3::x(), Material::color([0.8, 0.1, 0.1], 1.0)),
+            (Vector3::new(1.0, 0.0, 0.0), -Vector3::x(), Material::color([0.1, 0.8, 0.1], 1.0)),
+            (Vector3::new(0.0, -1.0, 0.0), Vector3::y(), white.clone()),
+            (Vector3::new(0.0, 1.0, 0.0), -Vector3::y(), Material::none().with_emission([1.0; 3])),
+            (Vector3::new(0.0, 0.0, -4.0), Vector3::z(), white.clone()),
+            (Vector3::new(0.0, 0.0, 1.0), -Vector3::z(), white),
+        ];
+        for (origin, normal, material) in walls.iter().cloned() {
+            scene.push_object(Plane::new(origin, normal, material));
+        }
+        // and a light under it for Whitted
+        scene.push_light(Light::new(Vector3::new(0.0, 0.9, -3.0), 1.0));
+
+        let render = |mode: RenderMode| {
+            let settings = RenderSettings { width: 24, height: 24, mode, ..Default::default() };
+            scene.render(&mut Framebuffer::new(24, 24), &settings)
+        };
+        // pixels of the floor, towards the red wall, in the middle or towards the green wall
+        let floor = |image: &Framebuffer, xs: std::ops::Range<usize>| {
+            (20..23)
+                .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .map(|(x, y)| image.get_pixel(x, y))
+                .collect::<Vec<_>>()
+        };
+        let mean = |pixels: &[[f32; 3]], channel: usize| {
+            pixels.iter().map(|pixel| pixel[channel]).sum::<f32>() / pixels.len() as f32
+        };
+        let variance = |pixels: &[[f32; 3]]| {
+            let mean = mean(pixels, 0);
+            pixels.iter().map(|pixel| (pixel[0] - mean).powi(2)).sum::<f32>() / pixels.len() as f32
+        };
+
+        // light bouncing off the colored walls tints the white floor next to them, which
+        // direct lighting alone leaves neutral
+        let direct = floor(&render(RenderMode::Whitted), 6..9);
+        assert_eq!(mean(&direct, 0), mean(&direct, 1));
+        let converged = render(RenderMode::PathTraced { samples: 64 });
+        let (left, right) = (floor(&converged, 6..9), floor(&converged, 15..18));
+        assert!(mean(&left, 0) > mean(&left, 1) + 0.01, "{:?}", left);
+        assert!(mean(&right, 1) > mean(&right, 0) + 0.01, "{:?}", right);
+
+        // and the noise settles with more samples
+        let noisy = floor(&render(RenderMode::PathTraced { samples: 4 }), 10..14);
+        let settled = floor(&converged, 10..14);
+        assert!(variance(&settled) < variance(&noisy) / 4.0);
+    }
+
+
+    #[test]
+    fn roulette_keeps_the_mean() {
+        let image_mean = |roulette: bool| {
+            let mut scene = Scene::new();
+            scene.set_max_depth(12);
+            scene.set_hdr(true);
+            scene.set_russian_roulette(roulette);
+            scene.set_background(Background::Solid([0.0; 3]));
+            // half-silvered mirrors, so each reflection of the glowing ball is dimmer than the
+            // last and roulette drops many of the deep ones
+            let mirror = Material::none().with_reflect(0.5);
+            let sides = (Vector3::y() * 2.0, -Vector3::z() * 20.0);
+            for &x in &[-1.0, 1.0] {
+                scene.push_object(Quad::new(Vector3::new(x, -1.0, 0.0), sides, mirror.clone()));
+            }
+            let glow = Material::none().with_emission([1.0; 3]);
+            scene.push_object(Sphere::new(Vector3::new(0.0, 0.0, -6.0), 0.3, glow));
+            let settings = RenderSettings {
+                width: 48,
+                height: 32,
+                aa_samples: 4,
+                ..RenderSettings::default()
+            };
+            let image = scene.render(&mut Framebuffer::new(48, 32), &settings);
+            image.buf().iter().map(|pixel| pixel[0]).sum::<f32>() / image.buf().len() as f32
+        };
+        let (full, roulette) = (image_mean(false), image_mean(true));
+        // roulette does change the render, but not its mean
+        assert_ne!(full, roulette);
+        assert!(full > 0.0 && (roulette - full).abs() < full * 0.02, "{} vs {}", full, roulette);
+    }
+
+
+    #[test]
+    fn moving_sphere_smears_sideways() {
+        let lit_columns = |velocity: Vector3<f32>| {
+            let mut scene = Scene::new();
+            scene.set_background(Background::Solid([0.0; 3]));
+            let glow = Material::none().with_emission([1.0; 3]);
+            let sphere = Sphere::new(Vector3::new(0.0, 0.0, -5.0), 0.5, glow);
+            scene.push_object(Moving::new(sphere, velocity));
+            let settings = RenderSettings {
+                width: 48,
+                height: 32,
+                aa_samples: 4,
+                shutter: Some((0.0, 1.0)),
+                ..RenderSettings::default()
+            };
+            let image = scene.render(&mut Framebuffer::new(48, 32), &settings);
+            // columns of the middle row the sphere shows up in at all
+            (0..48).filter(|&x| image.get_pixel(x, 16)[0] > 0.0).count()
+        };
+        let (still, moving) = (lit_columns(nalgebra::zero()), lit_columns(Vector3::x() * 2.0));
+        assert!(still > 0 && moving > still + 4, "{} vs {}", still, moving);
+    }
+
+
+    #[test]
+    fn fog_tints_toward_its_color() {
+        let scene_with_fog = |density: f32| {
+            let mut scene = Scene::new();
+            scene.set_background(Background::Solid([0.0, 0.0, 1.0]));
+            let (min, max) = (Vector3::new(-10.0, -10.0, -6.0), Vector3::new(10.0, 10.0, -4.0));
+            scene.push_volume(FogVolume::new(min, max, density, [1.0, 0.5, 0.0]));
+            scene
+        };
+
+        // a thick bank of fog hides the sky behind it
+        let color = scene_with_fog(10.0).cast_ray(Vector3::zeros(), -Vector3::z(), 4);
+        assert_eq!(color, [1.0, 0.5, 0.0]);
+
+        // while through thin fog some rays scatter and the rest carry on to the sky
+        let settings =
+            RenderSettings { width: 16, height: 16, aa_samples: 4, ..Default::default() };
+        let image = scene_with_fog(0.5).render(&mut Framebuffer::new(16, 16), &settings);
+        let mean = |channel: usize| {
+            image.buf().iter().map(|pixel| pixel[channel]).sum::<f32>() / image.buf().len() as f32
+        };
+        assert!(mean(0) > 0.3 && mean(0) < 0.9, "{}", mean(0));
+        assert!(mean(2) > 0.1 && mean(2) < 0.7, "{}", mean(2));
+    }
+
+
+    #[test]
+    fn fill_light_reaches_into_the_shadow() {
+        let shadowed_floor = |fill: Light| {
+            let mut scene = Scene::new();
+            let white = Material::color([1.0; 3], 1.0);
+            scene.push_object(Plane::new(nalgebra::zero(), Vector3::y(), white));
+            scene.push_object(Sphere::new(Vector3::new(0.0, 2.0, -5.0), 1.0, Material::none()));
+            // a red key light and a green fill light, both straight above the ball
+            scene.push_light(Light::colored(Vector3::new(0.0, 10.0, -5.0), [1.0, 0.0, 0.0]));
+            scene.push_light(fill);
+            let orig = Vector3::new(0.0, 1.0, 0.0);
+            scene.cast_ray(orig, (Vector3::new(0.0, 0.0, -5.0) - orig).normalize(), 1)
+        };
+        let fill = Light::colored(Vector3::new(0.0, 10.0, -5.0), [0.0, 1.0, 0.0]);
+        assert_eq!(shadowed_floor(fill.clone()), [0.0; 3]);
+        let color = shadowed_floor(fill.clone().with_shadows(false));
+        assert!(color[0] == 0.0 && color[1] > 0.9, "{:?}", color);
+        // or lets through a share of its light
+        let color = shadowed_floor(fill.with_shadow_color([0.0, 0.5, 0.0]));
+        assert!(color[0] == 0.0 && (color[1] - 0.5).abs() < 0.05, "{:?}", color);
+    }
+
+
+    #[test]
+    fn hemisphere_lights_floor_and_ceiling() {
+        let (sky, ground) = ([0.2, 0.4, 1.0], [0.5, 0.3, 0.1]);
+        let mut scene = Scene::new();
+        scene.set_ambient_hemisphere(sky, ground);
+        let white = Material::color([1.0; 3], 1.0);
+        scene.push_object(Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::y(), white.clone()));
+        scene.push_object(Plane::new(Vector3::new(0.0, 1.0, 0.0), -Vector3::y(), white));
+
+        // the floor faces the sky and the ceiling the ground
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-5);
+        let floor = scene.cast_ray(nalgebra::zero(), -Vector3::y(), 1);
+        assert!(close(floor, sky), "{:?}", floor);
+        let ceiling = scene.cast_ray(nalgebra::zero(), Vector3::y(), 1);
+        assert!(close(ceiling, ground), "{:?}", ceiling);
+    }
+
+
+    #[test]
+    fn environment_matches_empty_render() {
+        let mut scene = Scene::new();
+        scene.set_background(Background::Gradient { top: [0.0, 0.0, 1.0], bottom: [1.0; 3] });
+        // tilted up and to the side, so that the gradient runs across the image at an angle
+        let camera = Camera::new(nalgebra::zero(), Vector3::new(1.0, 0.5, -1.0), Vector3::y(), 1.0);
+        scene.set_camera(camera.clone());
+        let settings = RenderSettings { width: 24, height: 16, ..Default::default() };
+
+        let full = scene.render(&mut Framebuffer::new(24, 16), &settings);
+        let environment = scene.render_environment(&settings, &camera);
+        assert_eq!(environment.buf(), full.buf());
+        assert_eq!(environment.alpha(), full.alpha());
+    }
+
+
+    #[test]
+    fn adaptive_sampling_spends_rays_on_edges() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// A sphere counting the rays tested against it.
+        struct Counted(Sphere, Arc<AtomicUsize>);
+        impl Object for Counted {
+            fn ray_intersect(
+                &self,
+                orig: Vector3<f32>,
+                dir: Vector3<f32>,
+            ) -> Option<IntersectionInfo> {
+                self.1.fetch_add(1, Ordering::Relaxed);
+                self.0.ray_intersect(orig, dir)
+            }
+        }
+
+        // rays through a single pixel, looking at a narrow patch around `look_at`
+        let rays_for_pixel = |look_at: Vector3<f32>| {
+            let rays = Arc::new(AtomicUsize::new(0));
+            let glow = Material::none().with_emission([1.0; 3]);
+            let sphere = Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, glow);
+            let mut scene = Scene::new();
+            scene.set_background(Background::Solid([0.0; 3]));
+            scene.push_object(Counted(sphere, rays.clone()));
+            scene.set_camera(Camera::new(nalgebra::zero(), look_at, Vector3::y(), 0.01));
+            let adaptive = AdaptiveSampling { min_samples: 4, max_samples: 64, threshold: 0.01 };
+            let settings = RenderSettings {
+                width: 1,
+                height: 1,
+                adaptive: Some(adaptive),
+                ..Default::default()
+            };
+            scene.render(&mut Framebuffer::new(1, 1), &settings);
+            rays.load(Ordering::Relaxed)
+        };
+        // the middle of the sphere, and its silhouette seen from the origin
+        let flat = rays_for_pixel(Vector3::new(0.0, 0.0, -5.0));
+        let edge = rays_for_pixel(Vector3::new(5.0 * f32::tan(f32::asin(0.2)), 0.0, -5.0));
+        assert_eq!(flat, 4);
+        assert!(edge > 48, "{}", edge);
+    }
+
+
+    #[test]
+    fn ray_budget_cuts_the_render_short() {
+        // a glowing wall filling the view, so every rendered pixel is opaque
+        let mut scene = Scene::new();
+        let glow = Material::none().with_emission([1.0; 3]);
+        scene.push_object(Plane::new(Vector3::new(0.0, 0.0, -5.0), Vector3::z(), glow));
+        let render = |ray_budget: u64| {
+            let settings = RenderSettings {
+                width: 16,
+                height: 16,
+                ray_budget: Some(ray_budget),
+                ..Default::default()
+            };
+            let (image, truncated) = scene.render_bounded(&mut Framebuffer::new(16, 16), &settings);
+            let rendered = image.alpha().unwrap().iter().filter(|&&alpha| alpha == 1.0).count();
+            (rendered, truncated)
+        };
+
+        // one ray per pixel, in a single tile, so the budget runs out after exactly ten pixels
+        assert_eq!(render(10), (10, true));
+        assert_eq!(render(1_000_000), (256, false));
     }
 }
@@ -0,0 +1,88 @@
+use nalgebra::Vector3;
+use crate::{
+    camera::Camera,
+    material::Material,
+    object::{Object, Sphere},
+    scene::{Background, Light, Scene},
+};
+
+/// Fluent construction of a [`Scene`], taking positions as plain arrays.
+#[derive(Default)]
+pub struct SceneBuilder {
+    scene: Scene,
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn object<T: Object + 'static>(mut self, object: T) -> Self {
+        self.scene.push_object(object);
+        self
+    }
+
+    pub fn sphere(self, center: [f32; 3], radius: f32, material: Material) -> Self {
+        self.object(Sphere::new(Vector3::from(center), radius, material))
+    }
+
+    /// A point light with no falloff, as in [`Light::new`].
+    pub fn light(self, position: [f32; 3], intensity: f32) -> Self {
+        self.push_light(Light::new(Vector3::from(position), intensity))
+    }
+
+    pub fn push_light(mut self, light: Light) -> Self {
+        self.scene.push_light(light);
+        self
+    }
+
+    /// Camera at `position` looking towards `look_at`, with a vertical field of view of `fov`
+    /// radians.
+    pub fn camera(mut self, position: [f32; 3], look_at: [f32; 3], up: [f32; 3], fov: f32) -> Self {
+        self.scene.set_camera(Camera::new(
+            Vector3::from(position),
+            Vector3::from(look_at),
+            Vector3::from(up),
+            fov,
+        ));
+        self
+    }
+
+    pub fn background(mut self, background: Background) -> Self {
+        self.scene.set_background(background);
+        self
+    }
+
+    pub fn ambient(mut self, ambient: [f32; 3]) -> Self {
+        self.scene.set_ambient(ambient);
+        self
+    }
+
+    pub fn ambient_hemisphere(mut self, sky: [f32; 3], ground: [f32; 3]) -> Self {
+        self.scene.set_ambient_hemisphere(sky, ground);
+        self
+    }
+
+    pub fn build(self) -> Scene {
+        self.scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Plane;
+
+    #[test]
+    fn build_two_objects() {
+        let scene = SceneBuilder::new()
+            .sphere([0.0, 0.0, -16.0], 2.0, Material::none())
+            .object(Plane::new(Vector3::new(0.0, -4.0, 0.0), Vector3::y(), Material::none()))
+            .light([-20.0, 20.0, 20.0], 1.5)
+            .camera([0.0; 3], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0], 1.0)
+            .build();
+        assert_eq!(scene.objects_len(), 2);
+        assert_eq!(scene.iter_lights().count(), 1);
+        assert!(scene.camera().is_some());
+    }
+}
@@ -0,0 +1,199 @@
+use nalgebra::Vector3;
+use serde::Deserialize;
+use crate::{
+    camera::Camera,
+    material::Material,
+    object::{Checkerboard, Plane, Sphere},
+    scene::{Light, Scene},
+    RenderError,
+};
+
+#[derive(Debug, Deserialize)]
+struct SceneDesc {
+    camera: Option<CameraDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraDesc {
+    position: [f32; 3],
+    look_at: [f32; 3],
+    #[serde(default = "default_up")]
+    up: [f32; 3],
+    /// Vertical field of view in degrees.
+    fov: f32,
+}
+
+fn default_up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+#[derive(Debug, Deserialize)]
+struct LightDesc {
+    position: [f32; 3],
+    intensity: f32,
+    color: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ObjectDesc {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        material: MaterialDesc,
+    },
+    Checkerboard {
+        origin: [f32; 3],
+        cell_dir: ([f32; 3], [f32; 3]),
+        /// Tiles the whole plane when absent.
+        dims: Option<(u32, u32)>,
+        materials: (MaterialDesc, MaterialDesc),
+        #[serde(default)]
+        double_sided: bool,
+    },
+    Plane {
+        origin: [f32; 3],
+        normal: [f32; 3],
+        material: MaterialDesc,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MaterialDesc {
+    diffuse: Option<[f32; 3]>,
+    albedo: Option<f32>,
+    specular: Option<SpecularDesc>,
+    reflect: Option<f32>,
+    refract: Option<RefractDesc>,
+    emission: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpecularDesc {
+    exponent: f32,
+    albedo: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RefractDesc {
+    index: f32,
+    albedo: f32,
+}
+
+impl MaterialDesc {
+    fn build(&self) -> Material {
+        let mut material = match self.diffuse {
+            Some(color) => Material::color(color, self.albedo.unwrap_or(1.0)),
+            None => Material::none(),
+        };
+        if let Some(SpecularDesc { exponent, albedo }) = self.specular {
+            material = material.with_specular(exponent, albedo);
+        }
+        if let Some(albedo) = self.reflect {
+            material = material.with_reflect(albedo);
+        }
+        if let Some(RefractDesc { index, albedo }) = self.refract {
+            material = material.with_refract(index, albedo);
+        }
+        if let Some(color) = self.emission {
+            material = material.with_emission(color);
+        }
+        material
+    }
+}
+
+/// Reads a scene description in JSON, with spheres, checkerboards, planes, point lights and an
+/// optional camera.
+pub fn from_json<R: std::io::Read>(reader: R) -> Result<Scene, RenderError> {
+    let desc: SceneDesc =
+        serde_json::from_reader(reader).map_err(|err| RenderError::Parse(err.to_string()))?;
+
+    let mut scene = Scene::new();
+    if let Some(camera) = desc.camera {
+        scene.set_camera(Camera::new(
+            Vector3::from(camera.position),
+            Vector3::from(camera.look_at),
+            Vector3::from(camera.up),
+            camera.fov.to_radians(),
+        ));
+    }
+    for light in desc.lights {
+        let position = Vector3::from(light.position);
+        scene.push_light(match light.color {
+            Some(color) => Light::new(position, light.intensity)
+                .with_color((Vector3::from(color) * light.intensity).into()),
+            None => Light::new(position, light.intensity),
+        });
+    }
+    for object in desc.objects {
+        match object {
+            ObjectDesc::Sphere { center, radius, material } => {
+                scene.push_object(Sphere::new(Vector3::from(center), radius, material.build()));
+            },
+            ObjectDesc::Checkerboard { origin, cell_dir, dims, materials, double_sided } => {
+                let origin = Vector3::from(origin);
+                let cell_dir = (Vector3::from(cell_dir.0), Vector3::from(cell_dir.1));
+                let materials = (materials.0.build(), materials.1.build());
+                let board = match dims {
+                    Some(dims) => Checkerboard::new(origin, cell_dir, dims, materials),
+                    None => Checkerboard::infinite(origin, cell_dir, materials),
+                };
+                if double_sided {
+                    scene.push_object(board.with_double_sided());
+                } else {
+                    scene.push_object(board);
+                }
+            },
+            ObjectDesc::Plane { origin, normal, material } => {
+                scene.push_object(Plane::new(
+                    Vector3::from(origin),
+                    Vector3::from(normal),
+                    material.build(),
+                ));
+            },
+        }
+    }
+    Ok(scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{framebuffer::Framebuffer, scene::RenderSettings};
+
+    #[test]
+    fn load_and_render() {
+        let json = r#"{
+            "camera": { "position": [0, 0, 0], "look_at": [0, 0, -1], "fov": 60 },
+            "lights": [{ "position": [-20, 20, 20], "intensity": 1.5 }],
+            "objects": [{
+                "type": "sphere",
+                "center": [0, 0, -16],
+                "radius": 2,
+                "material": {
+                    "diffuse": [0.4, 0.4, 0.3],
+                    "specular": { "exponent": 50, "albedo": 0.3 }
+                }
+            }]
+        }"#;
+        let scene = from_json(json.as_bytes()).unwrap();
+        assert_eq!(scene.objects_len(), 1);
+        assert_eq!(scene.iter_lights().count(), 1);
+
+        let settings = RenderSettings { width: 8, height: 8, ..RenderSettings::default() };
+        scene.render(&mut Framebuffer::new(8, 8), &settings);
+    }
+
+    #[test]
+    fn bad_input_is_parse_error() {
+        match from_json(&b"{ \"objects\": 3 }"[..]) {
+            Err(RenderError::Parse(_)) => {},
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use tiny_raytracer::Framebuffer;
+
+#[test]
+fn renders_demo_scene() {
+    let file_name = format!("tiny-raytracer-cli-{}.png", std::process::id());
+    let output = std::env::temp_dir().join(file_name);
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_tiny-raytracer"))
+        .args(["--width", "64", "--height", "48", "--output"])
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let image = Framebuffer::read_png(std::fs::File::open(&output).unwrap()).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!((image.width(), image.height()), (64, 48));
+    assert!(image.buf().iter().any(|pixel| pixel.iter().any(|&c| c > 0.0)));
+}